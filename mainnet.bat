@@ -1,4 +1,9 @@
 set NEAR_ENV=mainnet
 set CONTRACT_ID=v1.havenswap.near
+set OWNER_ID=swap.genadrop.near
+set FEE_WALLET=fee.genadrop.near
+set HOLDER_COLLECTION=mint.havendao.near
 
-near deploy --wasmFile target/out/main.wasm --accountId %CONTRACT_ID%
\ No newline at end of file
+rem the state layout is not compatible with the original contract, so CONTRACT_ID has to be a fresh account.
+rem deploy and init run as one batch, on an account that still holds old state the whole batch fails
+near deploy --wasmFile target/out/main.wasm --accountId %CONTRACT_ID% --initFunction new --initArgs "{\"owner_id\":\"%OWNER_ID%\",\"fee_wallet\":\"%FEE_WALLET%\",\"required_cost\":\"100000000000000000000000\",\"holder_collection\":\"%HOLDER_COLLECTION%\",\"archive_retention\":null,\"timelock_delay\":null}"
\ No newline at end of file
@@ -1,6 +1,9 @@
+// offer callbacks carry the full set of offer terms
+#![allow(clippy::too_many_arguments)]
+
 use crate::*;
 use near_sdk::{env, Promise, ext_contract, Gas, PromiseOrValue, assert_one_yocto, PromiseResult};
-use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::borsh::{BorshDeserialize, BorshSerialize};
 use::near_sdk::serde::{Serialize, Deserialize};
 use near_sdk::AccountId;
 use std::collections::HashMap;
@@ -8,9 +11,10 @@ use std::collections::HashMap;
 
 pub type SalePriceInYoctoNear = U128;
 
-const GAS_FOR_NFT_TRANSFER: Gas = Gas(30_000_000_000_000);
+const GAS_FOR_NFT_TRANSFER: Gas = Gas::from_tgas(30);
 
 #[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Default, Clone)]
+#[borsh(crate = "near_sdk::borsh")]
 #[serde(crate = "near_sdk::serde")]
 pub struct TokenMetadata {
 
@@ -31,12 +35,38 @@ pub struct Token {
     pub royalty: Option<HashMap<AccountId, u32>>,
 }
 
+// the part of a NEP-171 token the ownership check needs
+#[derive(Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct TokenOwner {
+	pub owner_id: AccountId,
+}
+
 #[derive(BorshDeserialize, BorshSerialize)]
+#[borsh(crate = "near_sdk::borsh")]
 pub struct SaleArgs {
     pub owner: AccountId,
 	pub hash: String,
 }
 
+// `msg` of nft_transfer_call, anything that isn't one of these is read as an offer hash
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TransferMsg {
+	Offer { hash: Hash },
+	Listing { price: U128 },
+	DutchListing { start_price: U128, floor_price: U128, duration: U64, decay: PriceDecay },
+	Auction { reserve_price: U128, min_increment: U128, duration: U64 },
+	Bid { bid_id: u64 },
+}
+
+impl TransferMsg {
+	pub fn parse(msg: &str) -> Self {
+		near_sdk::serde_json::from_str(msg).unwrap_or_else(|_| TransferMsg::Offer { hash: msg.to_string() })
+	}
+}
+
 #[ext_contract(ext_self)]
 pub trait ExtSelf {
     fn callback_send_offer(&mut self,
@@ -47,47 +77,37 @@ pub trait ExtSelf {
 	receiver_id: AccountId,
 	receiver_nfts: Vec<TokenData>,
 	is_holder: bool,
+	deposit: U128,
 	) -> bool;
 }
 
 #[ext_contract(ext_nft_contract)]
-trait NFTContract {
+pub trait NFTContract {
     fn nft_transfer(&mut self, receiver_id: AccountId, token_id: String);
     fn nft_token(&self, token_id: String) -> Option<Token>;
 	fn nft_tokens_for_owner(&self, account_id: AccountId, from_index: String, limit: u16) -> Vec<Token>;
 }
 
+// NEP-171 nft_transfer panics unless exactly 1 yoctoNEAR is attached
+pub(crate) fn nft_transfer(token: &TokenData, receiver_id: &AccountId) -> Promise {
+	ext_nft_contract::ext(token.contract_id.clone())
+		.with_attached_deposit(NearToken::from_yoctonear(1))
+		.nft_transfer(receiver_id.clone(), token.token_id.clone())
+}
+
 #[near_bindgen]
 impl Contract {
 
 	pub fn get_hashes_for_owner(&self, owner_id: AccountId) -> Vec<String> {
-		let hashes_for_owner_set = self.hashes_per_owner.get(&owner_id);
-
-		if let Some(hashes_for_owner_set) = hashes_for_owner_set {
-			return hashes_for_owner_set;
-		} else {
-			return vec![];
-		};
+		self.hashes_per_owner.get(&owner_id).unwrap_or_default()
 	}
 
 	pub fn get_tokens_for_owner(&self, owner_id: AccountId) -> Vec<TokenData> {
-		let tokens_per_owner_set = self.tokens_per_owner.get(&owner_id);
-
-		if let Some(tokens_per_owner_set) = tokens_per_owner_set {
-			return tokens_per_owner_set;
-		} else {
-			return vec![];
-		};
+		self.tokens_per_owner.get(&owner_id).unwrap_or_default()
 	}
 
 	pub fn get_transaction_data(&self, hash: Hash) -> Option<HashOffer> {
-		let transaction_data = self.hash_map.get(&hash);
-
-		if let Some(transaction_data) = transaction_data {
-			return Some(transaction_data);
-		} else {
-			return None;
-		};
+		self.hash_map.get(&hash)
 	}
 
 	#[payable]
@@ -95,10 +115,12 @@ impl Contract {
 		&mut self,
 		receiver_id: AccountId,
 	) {
-		let attached_deposit = env::attached_deposit();
-		Promise::new(receiver_id).transfer(attached_deposit);
+		let attached_deposit = env::attached_deposit().as_yoctonear();
+		Promise::new(receiver_id).transfer(NearToken::from_yoctonear(attached_deposit));
 	}
 
+	// stores the offer once the holder lookup and the nft_token checks are in. anything that keeps the
+	// offer from being stored refunds the deposit instead of panicking, since the NEAR is already here
 	#[private]
 	pub fn callback_send_offer(&mut self, hash: String,
 		sender_id: AccountId,
@@ -106,68 +128,62 @@ impl Contract {
 		sender_nfts: Vec<TokenData>,
 		receiver_id: AccountId,
 		receiver_nfts: Vec<TokenData>,
-		is_holder: bool) -> bool {
-		
-		match env::promise_result(0) {
-			PromiseResult::NotReady => unreachable!(),
-			PromiseResult::Successful(val) => {
-
-				if let Ok(result) = near_sdk::serde_json::from_slice::<Vec<Token>>(&val) {
-					let is_monarch = if result.len() > 0 {
-						true
-					} else {
-						false
-					};
-					
-					assert_eq!(
-						is_holder,
-						is_monarch,
-						"Invalid holder status."
-					);
-
-					let account_id = &receiver_id;
-
-					let transaction_data = HashOffer {
-						sender_id: sender_id.clone(),
-						sender_near: u128::from(sender_near),
-						sender_nfts,
-						sent_nfts: Vec::new(),
-						receiver_id: receiver_id.clone(),
-						receiver_nfts,
-						received_nfts: Vec::new(),
-						timestamp: env::block_timestamp(),
-						is_monarch,
-					};
-
-					self.hash_map.insert(&hash, &transaction_data);
-
-					let mut hash_set = self.hashes_per_owner.get(&sender_id).unwrap_or_else(|| {
-						Vec::new()
-					});
-
-					hash_set.push(hash.clone());
-
-					let mut receiver_hash_set = self.hashes_per_owner.get(&account_id).unwrap_or_else(|| {
-						Vec::new()
-					});
-
-					receiver_hash_set.push(hash.clone());
-
-					self.hashes_per_owner.insert(&sender_id, &hash_set);
-					self.hashes_per_owner.insert(&account_id, &receiver_hash_set);
-
-					env::log_str(format!("Added offer: {}", &hash).as_str());
-					true
-				}
-				else {
-					env::panic_str("ERR_WRONG_VAL_RECEIVED");
-				}
-			},
-			PromiseResult::Failed => env::panic_str("ERR_CALLBACK_FAILED"),
+		is_holder: bool,
+		deposit: U128) -> bool {
+
+		let reject = |reason: &str| {
+			Promise::new(sender_id.clone()).transfer(NearToken::from_yoctonear(deposit.0));
+			env::log_str(format!("Offer {} rejected, {}", hash, reason).as_str());
+			false
+		};
+
+		// the hash could have been taken while the lookups were in flight
+		if self.hash_map.contains_key(&hash) {
+			return reject("offer already exists")
 		}
 
+		let is_monarch = promise_is_holder(0);
 
-		
+		if is_holder != is_monarch {
+			return reject("invalid holder status")
+		}
+
+		// results 1.. are the nft_token lookups
+		if !promise_owners_match(1, &sender_id, &sender_nfts, &receiver_id, &receiver_nfts) {
+			return reject("listed tokens are not owned by the offer parties")
+		}
+
+		// the fee schedule can change between send_offer and this callback
+		let fee_paid = self.internal_fee_for(sender_near.0, is_monarch);
+
+		if deposit.0 < sender_near.0 + fee_paid {
+			return reject("deposit no longer covers the fee")
+		}
+
+		let excess = deposit.0 - sender_near.0 - fee_paid;
+
+		if excess > 0 {
+			Promise::new(sender_id.clone()).transfer(NearToken::from_yoctonear(excess));
+		}
+
+		let transaction_data = HashOffer {
+			sender_id,
+			sender_near: sender_near.0,
+			sender_nfts,
+			sent_nfts: Vec::new(),
+			receiver_id,
+			receiver_nfts,
+			received_nfts: Vec::new(),
+			timestamp: env::block_timestamp(),
+			is_monarch,
+			fee_paid,
+			base_fee: self.required_cost.0,
+			expires_at: self.internal_expiry_deadline(),
+			status: OfferStatus::Created,
+		};
+
+		self.internal_add_offer(&hash, &transaction_data);
+		true
 	}
 
 	#[payable]
@@ -181,8 +197,10 @@ impl Contract {
 		receiver_nfts: Vec<TokenData>,
 		is_holder: bool,
 	) -> Promise {
-		let account = env::signer_account_id();
-		let attached_deposit = env::attached_deposit();
+		self.assert_offers_not_paused();
+
+		let account = env::predecessor_account_id();
+		let attached_deposit = env::attached_deposit().as_yoctonear();
 		let required_cost = u128::from(self.required_cost);
 
 		assert!(
@@ -196,22 +214,9 @@ impl Contract {
 			"Offer already exists",
 		);
 
-		if sender_near.0 < 10000000000000000000000000 || is_holder == true { // if below 10N collect 0.1N base fee
-			if attached_deposit < sender_near.0 + required_cost {
-				env::panic_str("Insufficient near attached");
-			}
-			
-		}
-
-		if is_holder == false {
-			if sender_near.0 >= 10000000000000000000000000 { // if more than or equal to 10N collect 1% fee
-				let tax = 100 as u128 * sender_near.clone().0 / 10_000u128;
-
-				if attached_deposit < sender_near.0 + tax {
-					env::panic_str("Insufficient near attached");
-				}
-
-			}
+		// base fee below the percentage threshold or for holders, percentage fee above it
+		if attached_deposit < sender_near.0 + self.internal_fee_for(sender_near.0, is_holder) {
+			env::panic_str("Insufficient near attached");
 		}
 
 		assert_eq!(
@@ -226,19 +231,36 @@ impl Contract {
 			"Can't be receiver"
 		);
 
+		self.assert_not_blocked(&account);
+		self.assert_not_blocked(&receiver_id);
+
 		assert!(
-			(sender_nfts.len() + receiver_nfts.len()) < 9,
-			"Maximum NFTS per transaction is 8"
+			(sender_nfts.len() + receiver_nfts.len()) <= MAX_NFTS_PER_OFFER,
+			"Maximum NFTS per transaction is {}",
+			MAX_NFTS_PER_OFFER
 		);
 
-		// let offer_amount = attached_deposit - required_cost;
+		for nfts_data in sender_nfts.iter().chain(receiver_nfts.iter()) {
+			self.assert_collection_allowed(&nfts_data.contract_id);
+		}
 
-		let promise = ext_nft_contract::ext(AccountId::try_from("mint.havendao.near".to_string()).unwrap()).nft_tokens_for_owner(account.clone(), format!("0"), 1);
+		self.assert_hash_binding(&hash, &OfferTerms {
+			sender_id: sender_id.clone(),
+			sender_near,
+			sender_nfts: sender_nfts.clone(),
+			receiver_id: receiver_id.clone(),
+			receiver_nfts: receiver_nfts.clone(),
+		});
 
+		// let offer_amount = attached_deposit - required_cost;
+
+		let promise = nft_token_lookups(&sender_nfts, &receiver_nfts)
+			.into_iter()
+			.fold(ext_nft_contract::ext(self.holder_collection.clone()).nft_tokens_for_owner(account, "0".to_string(), 1), Promise::and);
 
 		promise.then(
 			Self::ext(env::current_account_id()).with_static_gas(GAS_FOR_NFT_TRANSFER)
-			.callback_send_offer(hash, sender_id, sender_near, sender_nfts, receiver_id, receiver_nfts, is_holder)
+			.callback_send_offer(hash, sender_id, sender_near, sender_nfts, receiver_id, receiver_nfts, is_holder, U128(attached_deposit))
 		)
 
 
@@ -282,61 +304,85 @@ impl Contract {
 
 	// 	self.user_deposits.remove(&target_id);
 
-	// 	Promise::new(target_id).transfer(u128::from(stored_amount));
+	// 	Promise::new(target_id).transfer(NearToken::from_yoctonear(u128::from(stored_amount)));
 	// 	env::log_str(format!("Sent {:?} yoctoNEAR", stored_amount).as_str())
 	// }
 
 	#[payable]
+	#[allow(unused_variables)]
 	pub fn nft_on_transfer(
 		&mut self,
 		sender_id: AccountId,
 		previous_owner_id: AccountId,
         token_id: TokenId,
-        msg: Hash,
+        msg: String,
 	) -> PromiseOrValue<bool> {
 		// get the contract ID which is the predecessor
         let nft_contract_id = env::predecessor_account_id();
-        //get the signer which is the person who initiated the transaction
-        let signer_id = env::signer_account_id();
-
-        //make sure that the signer isn't the predecessor. This is so that we're sure
-        //this was called via a cross-contract call
-        assert_ne!(
-            nft_contract_id,
-            signer_id,
-            "nft_on_transfer should only be called via cross-contract call"
-        );
-
-		//make sure the owner ID is the signer. 
-        assert_eq!(
-            previous_owner_id,
-            signer_id,
-            "owner_id should be signer_id"
-        );
+        //the depositor is whoever owned the token, this can be a DAO or multisig acting through a proposal.
+        //previous_owner_id is whatever the calling contract reports and can't be verified here, trusting it
+        //rests entirely on the collection allowlist below. outside AllowlistOnly mode any contract can claim any depositor
+        let owner_id = previous_owner_id;
+
+		// hand the token straight back while deposits are paused
+		if self.pause.deposits {
+			env::log_str("deposits are paused, returning token");
+			return PromiseOrValue::Value(true)
+		}
+
+		if self.blocked_accounts.contains(&owner_id) {
+			env::log_str("account is blocked, returning token");
+			return PromiseOrValue::Value(true)
+		}
+
+		self.assert_collection_allowed(&nft_contract_id);
+
+		let hash = match TransferMsg::parse(&msg) {
+			TransferMsg::Offer { hash } => hash,
+			TransferMsg::Listing { price } => {
+				return self.internal_deposit_listing(owner_id, TokenData { contract_id: nft_contract_id, token_id }, ListingPrice::Fixed { price })
+			},
+			TransferMsg::DutchListing { start_price, floor_price, duration, decay } => {
+				let token = TokenData { contract_id: nft_contract_id, token_id };
+				return self.internal_deposit_listing(owner_id, token, ListingPrice::Dutch { start_price, floor_price, duration, decay })
+			},
+			TransferMsg::Auction { reserve_price, min_increment, duration } => {
+				let token = TokenData { contract_id: nft_contract_id, token_id };
+				return self.internal_deposit_auction(owner_id, token, reserve_price, min_increment, duration)
+			},
+			TransferMsg::Bid { bid_id } => {
+				return self.internal_accept_bid(owner_id, TokenData { contract_id: nft_contract_id, token_id }, bid_id)
+			},
+		};
 		
-		let hash_set = self.hashes_per_owner.get(&signer_id);
+		let hash_set = self.hashes_per_owner.get(&owner_id);
 
 		assert!(
 			hash_set.is_some(),
 			"{} is not initialized",
-			signer_id
+			owner_id
 		);
 
 		let hash_vec = hash_set.unwrap();
 
 		assert!(
-			hash_vec.contains(&msg),
+			hash_vec.contains(&hash),
 			"Hash not found!"
 		);
 
-		let mut hash_transaction = self.hash_map.get(&msg).unwrap();
+		let mut hash_transaction = self.hash_map.get(&hash).unwrap();
 
 		assert!(
-			signer_id == hash_transaction.sender_id || signer_id == hash_transaction.receiver_id,
-			"Signer is not sender or receiver",
+			owner_id == hash_transaction.sender_id || owner_id == hash_transaction.receiver_id,
+			"Owner is not sender or receiver",
 		);
 
-		if &signer_id == &hash_transaction.sender_id {
+		if self.internal_is_past_deadline(&hash_transaction) {
+			env::log_str("offer has expired, returning token");
+			return PromiseOrValue::Value(true)
+		}
+
+		if owner_id == hash_transaction.sender_id {
 			let expected_nfts = hash_transaction.sender_nfts.clone();
 
 			let found_token: Vec<TokenData> = expected_nfts.clone()
@@ -345,26 +391,18 @@ impl Contract {
 				.collect();
 
 			assert!(
-				found_token.len() > 0,
+				!found_token.is_empty(),
 				"Wrong nft sent"
 			);
 
 			let token_data = TokenData { contract_id: nft_contract_id.clone(), token_id: token_id.clone() };
 
-			hash_transaction.sent_nfts.push(token_data.clone());
-
-			self.hash_map.insert(&msg, &hash_transaction);
+			self.internal_escrow_token(&owner_id, &token_data, EscrowHolder::Offer(hash.clone()));
 
-			let mut tokens_per_owner_vec = self.tokens_per_owner.get(&signer_id).unwrap_or_else(|| {
-				Vec::new()
-			});
-
-			tokens_per_owner_vec.push(token_data);
-
-			self.tokens_per_owner.insert(&signer_id, &tokens_per_owner_vec);
+			hash_transaction.sent_nfts.push(token_data);
 
 		}
-		else if &signer_id == &hash_transaction.receiver_id {
+		else if owner_id == hash_transaction.receiver_id {
 			let expected_nfts = hash_transaction.receiver_nfts.clone();
 
 			let found_token: Vec<TokenData> = expected_nfts.clone()
@@ -373,195 +411,156 @@ impl Contract {
 				.collect();
 
 			assert!(
-				found_token.len() > 0,
+				!found_token.is_empty(),
 				"Wrong nft sent"
 			);
 
 			let token_data = TokenData { contract_id: nft_contract_id.clone(), token_id: token_id.clone() };
 
-			hash_transaction.received_nfts.push(token_data.clone());
-
-			self.hash_map.insert(&msg, &hash_transaction);
+			self.internal_escrow_token(&owner_id, &token_data, EscrowHolder::Offer(hash.clone()));
 
-			let mut tokens_per_owner_vec = self.tokens_per_owner.get(&hash_transaction.receiver_id).unwrap_or_else(|| {
-				Vec::new()
-			});
-
-			tokens_per_owner_vec.push(token_data);
-
-			self.tokens_per_owner.insert(&hash_transaction.receiver_id, &tokens_per_owner_vec);
+			hash_transaction.received_nfts.push(token_data);
 
 		}
-			
-		let tx_stored = self.hash_map.get(&msg).unwrap();
-		let sent_nft_count = tx_stored.sent_nfts.len() as isize - tx_stored.sender_nfts.len() as isize ;
-		let receive_nft_count = tx_stored.received_nfts.len() as isize - tx_stored.receiver_nfts.len() as isize ;
 
-		if sent_nft_count != 0 {
-			env::log_str(format!("sender hasnt sent all nfts").as_str());
-			return PromiseOrValue::Value(false)
-		}
-
-		if receive_nft_count != 0 {
-			env::log_str(format!("receiver hasnt sent all nfts").as_str());
+		let next_status = if hash_transaction.is_funded() { OfferStatus::Funded } else { OfferStatus::PartiallyFunded };
+		self.internal_set_status(&mut hash_transaction, next_status);
+		self.hash_map.insert(&hash, &hash_transaction);
+			
+		emit_event(EventLogVariant::NftDeposited(vec![NftDepositedLog {
+			hash: hash.clone(),
+			owner_id,
+			contract_id: nft_contract_id,
+			token_id,
+		}]));
+
+		if hash_transaction.status != OfferStatus::Funded {
 			return PromiseOrValue::Value(false)
 		}
 
 		// all nfts have been sent
-		let sender_array = tx_stored.sent_nfts;
-		let receiver_array = tx_stored.received_nfts;
-
-		let temp_tokens_arr = self.tokens_per_owner.get(&tx_stored.sender_id);
+		emit_event(EventLogVariant::OfferFunded(vec![OfferFundedLog { hash: hash.clone() }]));
 
-		if temp_tokens_arr.is_some() {
-			let mut tokens_arr = self.tokens_per_owner.get(&tx_stored.sender_id).unwrap();
-			
-			for nfts_data in sender_array.iter() {
-				ext_nft_contract::ext(nfts_data.contract_id.clone()).nft_transfer(tx_stored.receiver_id.clone(), nfts_data.token_id.clone());
-				let tokens_index = tokens_arr.iter().position(|x| *x.token_id == nfts_data.token_id.clone()).unwrap();
-				tokens_arr.remove(tokens_index);
-			}
-
-			self.tokens_per_owner.remove(&tx_stored.sender_id);
-			self.tokens_per_owner.insert(&tx_stored.sender_id, &tokens_arr);
-		}
-
-		let temp_tokens_arr2 = self.tokens_per_owner.get(&tx_stored.receiver_id);
-
-		if temp_tokens_arr2.is_some() {
-			let mut tokens_arr2 = self.tokens_per_owner.get(&tx_stored.receiver_id).unwrap();
-
-			for nfts_data in receiver_array.iter() {
-				ext_nft_contract::ext(nfts_data.contract_id.clone()).nft_transfer(tx_stored.sender_id.clone(), nfts_data.token_id.clone());
-				let tokens_index2 = tokens_arr2.iter().position(|x| *x.token_id == nfts_data.token_id.clone()).unwrap();
-				tokens_arr2.remove(tokens_index2);
-			}
-
-			self.tokens_per_owner.remove(&tx_stored.receiver_id);
-			self.tokens_per_owner.insert(&tx_stored.receiver_id, &tokens_arr2);
+		if self.pause.settlement {
+			env::log_str("settlement is paused, offer stays funded");
+			return PromiseOrValue::Value(false)
 		}
 
-		let mut hash_arr = self.hashes_per_owner.get(&tx_stored.sender_id).unwrap();
-		let index = hash_arr.iter().position(|x| *x == msg.clone()).unwrap();
-		hash_arr.remove(index);
+		self.internal_settle_offer(&hash, &hash_transaction);
+		PromiseOrValue::Value(false)
+	}
 
-		self.hashes_per_owner.remove(&tx_stored.sender_id);
-		self.hashes_per_owner.insert(&tx_stored.sender_id, &hash_arr);
+	// settles a fully funded offer that was held back while settlement was paused, past its deadline it can only be cancelled
+	pub fn settle_offer(
+		&mut self,
+		hash: Hash,
+	) {
+		self.assert_settlement_not_paused();
 
+		let hash_transaction = self.hash_map.get(&hash).expect("Hash not found!");
 
-		let mut hash_arr2 = self.hashes_per_owner.get(&tx_stored.receiver_id).unwrap();
-		let index2 = hash_arr2.iter().position(|x| *x == msg.clone()).unwrap();
-		hash_arr2.remove(index2);
-		
-		self.hashes_per_owner.remove(&tx_stored.receiver_id);
-		self.hashes_per_owner.insert(&tx_stored.receiver_id, &hash_arr2);
-
-		let is_monarch = tx_stored.is_monarch;
-
-		if tx_stored.sender_near > 0 {
-			// transfer near to the muhfucker
-			let tax = 100 as u128 * tx_stored.sender_near / 10_000u128;
-
-			if tx_stored.sender_near >= 10000000000000000000000000 {
-				if is_monarch == false {
-					Promise::new(self.fee_wallet.clone()).transfer(tax);
-				}
-				else {
-					Promise::new(self.fee_wallet.clone()).transfer(self.required_cost.clone().0);
-				}
-			}
-			else {
-				Promise::new(self.fee_wallet.clone()).transfer(self.required_cost.clone().0);
-			}
+		assert!(
+			hash_transaction.status == OfferStatus::Funded,
+			"Offer is not fully funded"
+		);
 
-			Promise::new(tx_stored.receiver_id.clone()).transfer(tx_stored.sender_near);
-		}
-		else {
-			Promise::new(self.fee_wallet.clone()).transfer(self.required_cost.clone().0);
+		assert!(
+			!self.internal_is_past_deadline(&hash_transaction),
+			"Offer has expired"
+		);
 
-		}
-		
-		self.hash_map.remove(&msg);
-		return PromiseOrValue::Value(false)
+		self.internal_settle_offer(&hash, &hash_transaction);
 	}
 
-	#[payable]
-	pub fn cancel_offer(
+	// re-issues the transfers of a Failed offer, only the tokens still escrowed are sent again
+	pub fn retry_settlement(
 		&mut self,
 		hash: Hash,
 	) {
-		assert_one_yocto();
+		self.assert_settlement_not_paused();
 
-		let hash_transaction = self.hash_map.get(&hash).unwrap();
+		let hash_transaction = self.hash_map.get(&hash).expect("Hash not found!");
+		let account_id = env::predecessor_account_id();
 
-		let is_monarch = hash_transaction.is_monarch;
-
-
-		let signer_id = env::signer_account_id();
+		assert!(
+			account_id == hash_transaction.sender_id
+				|| account_id == hash_transaction.receiver_id
+				|| self.has_role(Role::Support, account_id),
+			"Caller is not sender, receiver or support"
+		);
 
-		if signer_id == env::current_account_id() {
+		assert!(
+			hash_transaction.status == OfferStatus::Failed,
+			"Offer settlement has not failed"
+		);
 
-			let signer_nfts = hash_transaction.sent_nfts;
-			let receiver_nfts = hash_transaction.received_nfts;
+		self.internal_settle_offer(&hash, &hash_transaction);
+	}
 
+	// runs once every settlement transfer has resolved, tokens that failed to move stay escrowed
+	// and the offer is marked Failed until `retry_settlement` gets them through
+	#[private]
+	pub fn resolve_settlement(
+		&mut self,
+		hash: Hash,
+	) -> bool {
+		let mut hash_transaction = self.hash_map.get(&hash).expect("Hash not found!");
 
-			if self.tokens_per_owner.get(&hash_transaction.sender_id).is_some() {
-				let mut tokens_arr = self.tokens_per_owner.get(&hash_transaction.sender_id).unwrap();
+		assert!(
+			hash_transaction.status == OfferStatus::Settling,
+			"Offer is not settling"
+		);
 
-				for nfts_data in signer_nfts.iter() {
-					let tokens_index = tokens_arr.iter().position(|x| *x.token_id == nfts_data.token_id.clone()).unwrap();
-					tokens_arr.remove(tokens_index);
-				}
+		let transfers: Vec<TokenData> = hash_transaction.sent_nfts.iter().chain(hash_transaction.received_nfts.iter()).cloned().collect();
+		let mut failed = Vec::new();
 
-				// remove token from storage
-				self.tokens_per_owner.remove(&hash_transaction.sender_id);
-				self.tokens_per_owner.insert(&hash_transaction.sender_id, &tokens_arr);
+		for (index, nfts_data) in transfers.into_iter().enumerate() {
+			match env::promise_result(index as u64) {
+				PromiseResult::Successful(_) => {},
+				_ => failed.push(nfts_data),
 			}
-			
-			if self.tokens_per_owner.get(&hash_transaction.receiver_id).is_some() {
-				let mut tokens_arr2 = self.tokens_per_owner.get(&hash_transaction.receiver_id).unwrap();
+		}
 
-				for nfts_data in receiver_nfts {
-					let tokens_index = tokens_arr2.iter().position(|x| *x.token_id == nfts_data.token_id.clone()).unwrap();
-					tokens_arr2.remove(tokens_index);
-				}
+		if failed.is_empty() {
+			self.internal_finish_settlement(&hash, &mut hash_transaction);
+			return true
+		}
 
-				// remove token from storage
-				self.tokens_per_owner.remove(&hash_transaction.receiver_id);
-				self.tokens_per_owner.insert(&hash_transaction.receiver_id, &tokens_arr2);
+		// whatever did move is no longer ours to return
+		for nfts_data in hash_transaction.sent_nfts.iter().chain(hash_transaction.received_nfts.iter()) {
+			if !failed.iter().any(|x| x.contract_id == nfts_data.contract_id && x.token_id == nfts_data.token_id) {
+				self.internal_release_token(nfts_data);
 			}
+		}
 
-			let mut hash_arr = self.hashes_per_owner.get(&hash_transaction.sender_id).unwrap();
-			let index = hash_arr.iter().position(|x| *x == hash.clone()).unwrap();
-			hash_arr.remove(index);
-
-			// remove hash from storage
-			self.hashes_per_owner.remove(&hash_transaction.sender_id);
-			self.hashes_per_owner.insert(&hash_transaction.sender_id, &hash_arr);
-
-			
-			let mut hash_arr = self.hashes_per_owner.get(&hash_transaction.receiver_id).unwrap();
-			let index2 = hash_arr.iter().position(|x| *x == hash.clone()).unwrap();
-			hash_arr.remove(index2);
+		let is_failed = |x: &TokenData| failed.iter().any(|y| y.contract_id == x.contract_id && y.token_id == x.token_id);
+		hash_transaction.sent_nfts.retain(is_failed);
+		hash_transaction.received_nfts.retain(is_failed);
 
-			// remove hash from storage
-			self.hashes_per_owner.remove(&hash_transaction.receiver_id);
-			self.hashes_per_owner.insert(&hash_transaction.receiver_id, &hash_arr);
+		self.internal_set_status(&mut hash_transaction, OfferStatus::Failed);
+		self.hash_map.insert(&hash, &hash_transaction);
 
-			self.hash_map.remove(&hash);
+		env::log_str(format!("Settlement of {} failed for {} tokens", hash, failed.len()).as_str());
+		false
+	}
 
-			env::log_str(format!("Cancelled transaction: {}", &hash).as_str());
-			return
-		}
+	#[payable]
+	pub fn cancel_offer(
+		&mut self,
+		hash: Hash,
+	) {
+		assert_one_yocto();
 
+		let mut hash_transaction = self.hash_map.get(&hash).unwrap();
 
+		let account_id = env::predecessor_account_id();
 
-		let hash_set = self.hashes_per_owner.get(&signer_id);
+		let hash_set = self.hashes_per_owner.get(&account_id);
 
 		assert!(
 			hash_set.is_some(),
 			"{} is not initialized",
-			signer_id
+			account_id
 		);
 
 		let hash_vec = hash_set.unwrap();
@@ -572,89 +571,105 @@ impl Contract {
 		);
 
 		assert!(
-			&signer_id == &hash_transaction.sender_id || &signer_id == &hash_transaction.receiver_id,
-			"Signer is not sender or receiver",
+			account_id == hash_transaction.sender_id || account_id == hash_transaction.receiver_id,
+			"Caller is not sender or receiver",
 		);
 
-		let signer_nfts = hash_transaction.sent_nfts;
-		let receiver_nfts = hash_transaction.received_nfts;
+		assert!(
+			hash_transaction.status != OfferStatus::Failed,
+			"Failed offers can only be retried with retry_settlement"
+		);
 
-		if self.tokens_per_owner.get(&hash_transaction.sender_id).is_some() {
-			let mut tokens_arr = self.tokens_per_owner.get(&hash_transaction.sender_id).unwrap();
+		self.internal_set_status(&mut hash_transaction, OfferStatus::Cancelled);
 
-			for nfts_data in signer_nfts.iter() {
-				ext_nft_contract::ext(nfts_data.contract_id.clone()).nft_transfer(hash_transaction.sender_id.clone(), nfts_data.token_id.clone());
-				// ext_nft_contract::nft_transfer(hash_transaction.sender_id.clone(), nfts_data.token_id.clone(), nfts_data.contract_id.clone(), 1, GAS_FOR_NFT_TRANSFER);
-				let tokens_index = tokens_arr.iter().position(|x| *x.token_id == nfts_data.token_id.clone()).unwrap();
-				tokens_arr.remove(tokens_index);
-			}
+		let returned_nfts = self.internal_return_escrow(&hash_transaction);
+		let refunded_near = self.internal_refund_closed_offer(&hash_transaction);
 
-			// remove token from storage
-			self.tokens_per_owner.remove(&hash_transaction.sender_id);
-			self.tokens_per_owner.insert(&hash_transaction.sender_id, &tokens_arr);
-		}
-		
-		
-		if self.tokens_per_owner.get(&hash_transaction.receiver_id).is_some() {
-			let mut tokens_arr2 = self.tokens_per_owner.get(&hash_transaction.receiver_id).unwrap();
-
-			for nfts_data in receiver_nfts.iter() {
-				ext_nft_contract::ext(nfts_data.contract_id.clone()).nft_transfer(hash_transaction.receiver_id.clone(), nfts_data.token_id.clone());
-				// ext_nft_contract::nft_transfer(hash_transaction.receiver_id.clone(), nfts_data.token_id.clone(), nfts_data.contract_id.clone(), 1, GAS_FOR_NFT_TRANSFER);
-				let tokens_index = tokens_arr2.iter().position(|x| *x.token_id == nfts_data.token_id.clone()).unwrap();
-				tokens_arr2.remove(tokens_index);
-			}
+		self.internal_delete_offer(&hash, &hash_transaction);
+		self.internal_archive_offer(&hash, &hash_transaction, OfferOutcome::Cancelled, hash_transaction.cancel_fee());
 
-			// remove token from storage
-			self.tokens_per_owner.remove(&hash_transaction.receiver_id);
-			self.tokens_per_owner.insert(&hash_transaction.receiver_id, &tokens_arr2);
-		}
+		emit_event(EventLogVariant::OfferCancelled(vec![OfferCancelledLog {
+			hash,
+			cancelled_by: account_id,
+			returned_nfts,
+			refunded_near: U128(refunded_near),
+			reason: None,
+		}]));
+	}
 
-		let mut hash_arr = self.hashes_per_owner.get(&hash_transaction.sender_id).unwrap();
-		let index = hash_arr.iter().position(|x| *x == hash.clone()).unwrap();
-		hash_arr.remove(index);
+	// support staff can unwind any open offer, every escrowed NFT and the full NEAR deposit go back to the depositors.
+	// Failed offers already delivered part of the swap, they are retried or go through `admin_unwind_failed_offer`
+	#[payable]
+	pub fn admin_cancel_offer(
+		&mut self,
+		hash: Hash,
+		reason: String,
+	) {
+		assert_one_yocto();
+		self.assert_role(Role::Support);
 
-		// remove hash from storage
-		self.hashes_per_owner.remove(&hash_transaction.sender_id);
-		self.hashes_per_owner.insert(&hash_transaction.sender_id, &hash_arr);
+		let mut hash_transaction = self.hash_map.get(&hash).expect("Hash not found!");
 
-		
-		let mut hash_arr = self.hashes_per_owner.get(&hash_transaction.receiver_id).unwrap();
-		let index2 = hash_arr.iter().position(|x| *x == hash.clone()).unwrap();
-		hash_arr.remove(index2);
+		assert!(
+			hash_transaction.status != OfferStatus::Failed,
+			"Failed offers are retried or unwound with admin_unwind_failed_offer"
+		);
 
-		// remove hash from storage
-		self.hashes_per_owner.remove(&hash_transaction.receiver_id);
-		self.hashes_per_owner.insert(&hash_transaction.receiver_id, &hash_arr);
+		self.internal_set_status(&mut hash_transaction, OfferStatus::Cancelled);
 
-		if hash_transaction.sender_near > 0 {
+		let returned_nfts = self.internal_return_escrow(&hash_transaction);
 
-			if hash_transaction.sender_near >= 10000000000000000000000000 { // if more than or equal to 10N collect 1% fee
-				let tax = 100 as u128 * hash_transaction.sender_near.clone() / 10_000u128;
+		// nothing was forwarded to the fee wallet yet, so the fee goes back as well
+		let refunded_near = hash_transaction.sender_near + hash_transaction.fee_paid;
+		Promise::new(hash_transaction.sender_id.clone()).transfer(NearToken::from_yoctonear(refunded_near));
 
-				if tax > self.required_cost.clone().0 {
-					let sent_amount = tax - self.required_cost.clone().0;
+		self.internal_delete_offer(&hash, &hash_transaction);
+		self.internal_archive_offer(&hash, &hash_transaction, OfferOutcome::Cancelled, 0);
 
-					if is_monarch {
-						Promise::new(hash_transaction.sender_id.clone()).transfer(self.required_cost.clone().0);
-					}
-					else {
-						Promise::new(hash_transaction.sender_id.clone()).transfer(sent_amount);
-					}
-				}
+		emit_event(EventLogVariant::OfferCancelled(vec![OfferCancelledLog {
+			hash,
+			cancelled_by: env::predecessor_account_id(),
+			returned_nfts,
+			refunded_near: U128(refunded_near),
+			reason: Some(reason),
+		}]));
+	}
 
-			}
-			Promise::new(self.fee_wallet.clone()).transfer(self.required_cost.clone().0);
+	// last resort for a Failed offer whose collection keeps rejecting the transfer: the tokens that didn't move
+	// go back to whoever deposited them and the sender gets the NEAR and fee back, nothing has been paid out yet
+	#[payable]
+	pub fn admin_unwind_failed_offer(
+		&mut self,
+		hash: Hash,
+		reason: String,
+	) {
+		assert_one_yocto();
+		self.assert_role(Role::Support);
 
-			Promise::new(hash_transaction.sender_id.clone()).transfer(hash_transaction.sender_near);
-		}
-		else {
-			Promise::new(self.fee_wallet.clone()).transfer(self.required_cost.clone().0);
-		}
+		let mut hash_transaction = self.hash_map.get(&hash).expect("Hash not found!");
 
-		self.hash_map.remove(&hash);
+		assert!(
+			hash_transaction.status == OfferStatus::Failed,
+			"Offer settlement has not failed"
+		);
 
-		env::log_str(format!("Cancelled transaction: {}", &hash).as_str());
+		self.internal_set_status(&mut hash_transaction, OfferStatus::Cancelled);
+
+		let returned_nfts = self.internal_return_escrow(&hash_transaction);
+
+		let refunded_near = hash_transaction.sender_near + hash_transaction.fee_paid;
+		Promise::new(hash_transaction.sender_id.clone()).transfer(NearToken::from_yoctonear(refunded_near));
+
+		self.internal_delete_offer(&hash, &hash_transaction);
+		self.internal_archive_offer(&hash, &hash_transaction, OfferOutcome::Cancelled, 0);
+
+		emit_event(EventLogVariant::OfferCancelled(vec![OfferCancelledLog {
+			hash,
+			cancelled_by: env::predecessor_account_id(),
+			returned_nfts,
+			refunded_near: U128(refunded_near),
+			reason: Some(reason),
+		}]));
 	}
 	// #[payable]
 	// pub fn deposit_deduct( // deduct per transaction
@@ -681,4 +696,49 @@ impl Contract {
 
 	// 	env::log_str(format!("Successfully deducted from {}", &target_id).as_str())
 	// }
-}
\ No newline at end of file
+}
+
+// whether the nft_tokens_for_owner call behind promise result `index` found a holder token,
+// a failed lookup counts as not holding one
+pub(crate) fn promise_is_holder(index: u64) -> bool {
+	match env::promise_result(index) {
+		PromiseResult::Successful(val) => near_sdk::serde_json::from_slice::<Vec<Token>>(&val)
+			.map(|result| !result.is_empty())
+			.unwrap_or(false),
+		_ => false,
+	}
+}
+
+// one nft_token call per listed token, sender's tokens first, so every token can be checked
+// against the party expected to deposit it
+pub(crate) fn nft_token_lookups(sender_nfts: &[TokenData], receiver_nfts: &[TokenData]) -> Vec<Promise> {
+	sender_nfts.iter()
+		.chain(receiver_nfts.iter())
+		.map(|nfts_data| ext_nft_contract::ext(nfts_data.contract_id.clone()).nft_token(nfts_data.token_id.clone()))
+		.collect()
+}
+
+// reads the results of `nft_token_lookups`, which start at promise result `first_index`
+pub(crate) fn promise_owners_match(
+	first_index: u64,
+	sender_id: &AccountId,
+	sender_nfts: &[TokenData],
+	receiver_id: &AccountId,
+	receiver_nfts: &[TokenData],
+) -> bool {
+	sender_nfts.iter().map(|_| sender_id)
+		.chain(receiver_nfts.iter().map(|_| receiver_id))
+		.enumerate()
+		.all(|(index, owner_id)| promise_token_owner(first_index + index as u64).as_ref() == Some(owner_id))
+}
+
+// owner reported by the nft_token call behind promise result `index`
+fn promise_token_owner(index: u64) -> Option<AccountId> {
+	match env::promise_result(index) {
+		PromiseResult::Successful(val) => near_sdk::serde_json::from_slice::<Option<TokenOwner>>(&val)
+			.ok()
+			.flatten()
+			.map(|token| token.owner_id),
+		_ => None,
+	}
+}
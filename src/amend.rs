@@ -0,0 +1,124 @@
+use crate::*;
+use near_sdk::Promise;
+
+#[near_bindgen]
+impl Contract {
+
+	// the sender rewrites their side of an offer until the receiver deposits anything.
+	// a higher sender_near (and fee) has to be attached, a lower one is refunded along with
+	// any deposited NFT that is no longer part of the offer.
+	// the new tokens skip the nft_token ownership check: they are all the sender's own, the offer can't fund
+	// until the sender deposits each of them, and the receiver has nothing escrowed yet, so a token the
+	// sender doesn't own only stalls an offer either party can cancel
+	#[payable]
+	pub fn amend_offer(
+		&mut self,
+		hash: Hash,
+		sender_nfts: Vec<TokenData>,
+		sender_near: U128,
+	) {
+		self.assert_offers_not_paused();
+
+		let mut hash_transaction = self.hash_map.get(&hash).expect("Hash not found!");
+		let attached_deposit = env::attached_deposit().as_yoctonear();
+
+		assert_eq!(
+			env::predecessor_account_id(),
+			hash_transaction.sender_id,
+			"Caller is not the sender"
+		);
+
+		assert!(
+			self.hash_mode != HashMode::Bound,
+			"Offers can't be amended while hashes are bound to their terms"
+		);
+
+		assert!(
+			matches!(hash_transaction.status, OfferStatus::Created | OfferStatus::PartiallyFunded)
+				&& hash_transaction.received_nfts.is_empty(),
+			"Offer can only be amended before the receiver deposits"
+		);
+
+		assert!(
+			!self.internal_is_past_deadline(&hash_transaction),
+			"Offer has expired"
+		);
+
+		assert!(
+			(sender_nfts.len() + hash_transaction.receiver_nfts.len()) <= MAX_NFTS_PER_OFFER,
+			"Maximum NFTS per transaction is {}",
+			MAX_NFTS_PER_OFFER
+		);
+
+		for nfts_data in sender_nfts.iter() {
+			self.assert_collection_allowed(&nfts_data.contract_id);
+		}
+
+		// the new terms are priced on the current fee schedule, what was paid for the old ones is credited
+		let fee = self.internal_fee_for(sender_near.0, hash_transaction.is_monarch);
+		let paid = hash_transaction.sender_near + hash_transaction.fee_paid;
+		let owed = sender_near.0 + fee;
+
+		assert!(
+			paid + attached_deposit >= owed,
+			"Must attach {} yoctoNEAR to cover the new terms",
+			owed - paid
+		);
+
+		let refunded_near = paid + attached_deposit - owed;
+
+		if refunded_near > 0 {
+			Promise::new(hash_transaction.sender_id.clone()).transfer(NearToken::from_yoctonear(refunded_near));
+		}
+
+		// deposited NFTs that were dropped from the offer go back to the sender
+		let (kept, returned_nfts): (Vec<TokenData>, Vec<TokenData>) = hash_transaction.sent_nfts
+			.drain(..)
+			.partition(|x| sender_nfts.iter().any(|y| y.contract_id == x.contract_id && y.token_id == x.token_id));
+
+		for nfts_data in returned_nfts.iter() {
+			self.internal_return_token(nfts_data);
+		}
+
+		hash_transaction.sent_nfts = kept;
+		hash_transaction.sender_nfts = sender_nfts;
+		hash_transaction.sender_near = sender_near.0;
+		hash_transaction.fee_paid = fee;
+		hash_transaction.base_fee = self.required_cost.0;
+
+		let next_status = if hash_transaction.is_funded() {
+			OfferStatus::Funded
+		} else if hash_transaction.sent_nfts.is_empty() {
+			OfferStatus::Created
+		} else {
+			OfferStatus::PartiallyFunded
+		};
+
+		if next_status != hash_transaction.status {
+			self.internal_set_status(&mut hash_transaction, next_status);
+		}
+
+		self.hash_map.insert(&hash, &hash_transaction);
+
+		emit_event(EventLogVariant::OfferAmended(vec![OfferAmendedLog {
+			hash: hash.clone(),
+			sender_id: hash_transaction.sender_id.clone(),
+			receiver_id: hash_transaction.receiver_id.clone(),
+			sender_near,
+			sender_nfts: hash_transaction.sender_nfts.clone(),
+			returned_nfts,
+			refunded_near: U128(refunded_near),
+		}]));
+
+		// dropping the last outstanding NFT can leave nothing to wait for
+		if hash_transaction.status == OfferStatus::Funded {
+			emit_event(EventLogVariant::OfferFunded(vec![OfferFundedLog { hash: hash.clone() }]));
+
+			if self.pause.settlement {
+				env::log_str("settlement is paused, offer stays funded");
+			} else {
+				self.internal_settle_offer(&hash, &hash_transaction);
+			}
+		}
+	}
+}
@@ -0,0 +1,205 @@
+use crate::*;
+use crate::internal::collections_of;
+use near_sdk::assert_one_yocto;
+use near_sdk::collections::Vector;
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub enum OfferOutcome {
+	Settled,
+	Cancelled,
+	Expired,
+}
+
+// compact record of a finished offer, kept after the offer leaves hash_map
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub struct ArchivedOffer {
+	pub id: u64,
+	pub hash: Hash,
+	pub outcome: OfferOutcome,
+	pub sender_id: AccountId,
+	pub sender_near: U128,
+	pub sender_nfts: Vec<TokenData>,
+	pub receiver_id: AccountId,
+	pub receiver_nfts: Vec<TokenData>,
+	pub fee: U128,
+	pub created_at: u64,
+	pub closed_at: u64,
+}
+
+// archived ids of one account or collection, oldest first; pruned ids sit before `first`
+#[derive(BorshDeserialize, BorshSerialize)]
+#[borsh(crate = "near_sdk::borsh")]
+pub struct ArchiveIndex {
+	pub first: u64,
+	pub ids: Vector<u64>,
+}
+
+#[near_bindgen]
+impl Contract {
+
+	pub fn get_archived_offer(&self, id: u64) -> Option<ArchivedOffer> {
+		self.archive.get(&id)
+	}
+
+	pub fn total_archived_offers(&self) -> u64 {
+		self.archive_next_id - self.archive_first_id
+	}
+
+	pub fn get_account_history(&self, account_id: AccountId, from_index: Option<U128>, limit: Option<u64>) -> Vec<ArchivedOffer> {
+		let index = self.archive_per_account.get(&account_id);
+		self.archive_page(index, from_index, limit)
+	}
+
+	pub fn get_collection_history(&self, contract_id: AccountId, from_index: Option<U128>, limit: Option<u64>) -> Vec<ArchivedOffer> {
+		let index = self.archive_per_collection.get(&contract_id);
+		self.archive_page(index, from_index, limit)
+	}
+
+	pub fn get_archive_retention(&self) -> Option<u64> {
+		self.archive_retention
+	}
+
+	// max_records of None keeps every record
+	#[payable]
+	pub fn set_archive_retention(&mut self, max_records: Option<u64>) {
+		assert_one_yocto();
+		self.assert_owner();
+		self.archive_retention = max_records;
+	}
+
+	// drops up to `limit` of the oldest records that exceed the retention policy
+	#[payable]
+	pub fn prune_archive(&mut self, limit: u64) -> u64 {
+		assert_one_yocto();
+		self.assert_owner();
+
+		let mut pruned = 0;
+		while pruned < limit && self.internal_prune_oldest() {
+			pruned += 1;
+		}
+
+		env::log_str(format!("Pruned {} archived offers", pruned).as_str());
+		pruned
+	}
+}
+
+impl Contract {
+	fn archive_page(&self, index: Option<ArchiveIndex>, from_index: Option<U128>, limit: Option<u64>) -> Vec<ArchivedOffer> {
+		let index = match index {
+			Some(index) => index,
+			None => return vec![],
+		};
+
+		let start = index.first.saturating_add(u128::from(from_index.unwrap_or(U128(0))) as u64);
+		let end = start.saturating_add(limit.unwrap_or(50)).min(index.ids.len());
+
+		(start..end)
+			.filter_map(|i| index.ids.get(i))
+			.filter_map(|id| self.archive.get(&id))
+			.collect()
+	}
+
+	pub(crate) fn internal_archive_offer(&mut self, hash: &Hash, offer: &HashOffer, outcome: OfferOutcome, fee: u128) {
+		let id = self.archive_next_id;
+		self.archive_next_id += 1;
+
+		let record = ArchivedOffer {
+			id,
+			hash: hash.clone(),
+			outcome,
+			sender_id: offer.sender_id.clone(),
+			sender_near: U128(offer.sender_near),
+			sender_nfts: offer.sender_nfts.clone(),
+			receiver_id: offer.receiver_id.clone(),
+			receiver_nfts: offer.receiver_nfts.clone(),
+			fee: U128(fee),
+			created_at: offer.timestamp,
+			closed_at: env::block_timestamp(),
+		};
+
+		self.archive.insert(&id, &record);
+		self.archive_by_hash.insert(hash, &id);
+
+		for account_id in [&record.sender_id, &record.receiver_id] {
+			push_archive_id(&mut self.archive_per_account, b"archive_account_ids", account_id, id);
+		}
+
+		for contract_id in archive_collections(&record) {
+			push_archive_id(&mut self.archive_per_collection, b"archive_collection_ids", &contract_id, id);
+		}
+
+		// keep the archive at its retention size, one record per new entry
+		self.internal_prune_oldest();
+	}
+
+	// removes the oldest record if the archive is over its retention size
+	fn internal_prune_oldest(&mut self) -> bool {
+		let max_records = match self.archive_retention {
+			Some(max_records) => max_records,
+			None => return false,
+		};
+
+		if self.total_archived_offers() <= max_records {
+			return false;
+		}
+
+		let id = self.archive_first_id;
+		self.archive_first_id += 1;
+
+		let record = match self.archive.remove(&id) {
+			Some(record) => record,
+			None => return true,
+		};
+
+		// a reused hash points at its newer record
+		if self.archive_by_hash.get(&record.hash) == Some(id) {
+			self.archive_by_hash.remove(&record.hash);
+		}
+
+		for account_id in [&record.sender_id, &record.receiver_id] {
+			remove_archive_id(&mut self.archive_per_account, account_id, id);
+		}
+
+		for contract_id in archive_collections(&record) {
+			remove_archive_id(&mut self.archive_per_collection, &contract_id, id);
+		}
+
+		true
+	}
+}
+
+fn archive_collections(record: &ArchivedOffer) -> Vec<AccountId> {
+	collections_of(record.sender_nfts.iter().chain(record.receiver_nfts.iter()))
+}
+
+fn push_archive_id(indexes: &mut LookupMap<AccountId, ArchiveIndex>, prefix: &[u8], key: &AccountId, id: u64) {
+	let mut index = indexes.get(key).unwrap_or_else(|| {
+		// hashing the key keeps one account's prefix from being the start of another's
+		let mut prefix = prefix.to_vec();
+		prefix.extend(env::sha256(key.as_bytes()));
+		ArchiveIndex { first: 0, ids: Vector::new(prefix) }
+	});
+
+	index.ids.push(&id);
+	indexes.insert(key, &index);
+}
+
+// ids are archived and pruned in order, so the pruned id is always the oldest one left in the index
+fn remove_archive_id(indexes: &mut LookupMap<AccountId, ArchiveIndex>, key: &AccountId, id: u64) {
+	if let Some(mut index) = indexes.get(key) {
+		while index.ids.get(index.first) == Some(id) {
+			index.first += 1;
+		}
+
+		// clearing would cost a write per id ever archived; a new index under the same prefix overwrites the slots
+		if index.first >= index.ids.len() {
+			indexes.remove(key);
+		} else {
+			indexes.insert(key, &index);
+		}
+	}
+}
@@ -0,0 +1,332 @@
+use crate::*;
+use near_sdk::{assert_one_yocto, Gas, Promise, PromiseOrValue, PromiseResult};
+
+const GAS_FOR_AUCTION_CALLBACK: Gas = Gas::from_tgas(10);
+const GAS_FOR_RESOLVE_AUCTION: Gas = Gas::from_tgas(20);
+
+// a bid in the last 10 minutes pushes the end out to 10 minutes after that bid
+pub const ANTI_SNIPING_WINDOW: u64 = 10 * 60 * 1_000_000_000;
+
+// longest an auction can run, keeps `ends_at` well inside u64
+pub const MAX_AUCTION_DURATION: u64 = 90 * 24 * 60 * 60 * 1_000_000_000;
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub struct AuctionBid {
+	pub bidder_id: AccountId,
+	pub amount: U128,
+	pub placed_at: u64,
+}
+
+// English auction for an escrowed NFT, the highest bid is held by the contract
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub struct Auction {
+	pub id: u64,
+	pub seller_id: AccountId,
+	pub token: TokenData,
+	pub reserve_price: U128,
+	pub min_increment: U128,
+	pub highest_bid: Option<AuctionBid>,
+	pub is_holder: bool,
+	pub starts_at: u64,
+	pub ends_at: u64,
+}
+
+impl Auction {
+	// smallest amount the next bid has to attach
+	pub fn min_bid(&self) -> u128 {
+		match &self.highest_bid {
+			Some(bid) => bid.amount.0 + self.min_increment.0,
+			None => self.reserve_price.0,
+		}
+	}
+}
+
+#[near_bindgen]
+impl Contract {
+
+	pub fn get_auction(&self, auction_id: u64) -> Option<Auction> {
+		self.auctions.get(&auction_id)
+	}
+
+	pub fn get_auctions(&self, from_index: Option<U128>, limit: Option<u64>) -> Vec<Auction> {
+		let start = u128::from(from_index.unwrap_or(U128(0)));
+
+		self.auctions
+			.values()
+			.skip(start as usize)
+			.take(limit.unwrap_or(50) as usize)
+			.collect()
+	}
+
+	// opens the auction once the seller's holder status is in, the escrowed token is kept for the winner
+	#[private]
+	pub fn callback_create_auction(
+		&mut self,
+		auction_id: u64,
+		seller_id: AccountId,
+		token: TokenData,
+		reserve_price: U128,
+		min_increment: U128,
+		duration: U64,
+	) -> bool {
+		let is_holder = promise_is_holder(0);
+
+		let now = env::block_timestamp();
+		let auction = Auction {
+			id: auction_id,
+			seller_id,
+			token,
+			reserve_price,
+			min_increment,
+			highest_bid: None,
+			is_holder,
+			starts_at: now,
+			ends_at: now + duration.0,
+		};
+
+		self.auctions.insert(&auction_id, &auction);
+
+		emit_event(EventLogVariant::AuctionCreated(vec![AuctionCreatedLog {
+			auction_id,
+			seller_id: auction.seller_id,
+			contract_id: auction.token.contract_id,
+			token_id: auction.token.token_id,
+			reserve_price,
+			min_increment,
+			ends_at: U64(auction.ends_at),
+		}]));
+
+		false
+	}
+
+	// the attached deposit is the bid, the previous highest bidder is refunded straight away
+	#[payable]
+	pub fn place_bid(
+		&mut self,
+		auction_id: u64,
+	) {
+		self.assert_offers_not_paused();
+
+		let mut auction = self.auctions.get(&auction_id).expect("Auction not found");
+		let bidder_id = env::predecessor_account_id();
+		let amount = env::attached_deposit().as_yoctonear();
+		let now = env::block_timestamp();
+
+		assert!(
+			now < auction.ends_at,
+			"Auction has ended"
+		);
+
+		assert_ne!(
+			bidder_id,
+			auction.seller_id,
+			"Seller can't bid on their own auction"
+		);
+
+		self.assert_not_blocked(&bidder_id);
+
+		assert!(
+			amount >= auction.min_bid(),
+			"Bid must be at least {} yoctoNEAR",
+			auction.min_bid()
+		);
+
+		if let Some(outbid) = auction.highest_bid.take() {
+			Promise::new(outbid.bidder_id).transfer(NearToken::from_yoctonear(outbid.amount.0));
+		}
+
+		auction.highest_bid = Some(AuctionBid { bidder_id: bidder_id.clone(), amount: U128(amount), placed_at: now });
+
+		if auction.ends_at - now < ANTI_SNIPING_WINDOW {
+			auction.ends_at = now + ANTI_SNIPING_WINDOW;
+		}
+
+		self.auctions.insert(&auction_id, &auction);
+
+		emit_event(EventLogVariant::AuctionBid(vec![AuctionBidLog {
+			auction_id,
+			bidder_id,
+			amount: U128(amount),
+			ends_at: U64(auction.ends_at),
+		}]));
+	}
+
+	// callable by anyone once the auction has ended, without a winning bid the token goes back to the seller
+	pub fn settle_auction(
+		&mut self,
+		auction_id: u64,
+	) -> PromiseOrValue<bool> {
+		self.assert_settlement_not_paused();
+
+		let auction = self.auctions.get(&auction_id).expect("Auction not found");
+
+		assert!(
+			env::block_timestamp() >= auction.ends_at,
+			"Auction is still running"
+		);
+
+		self.auctions.remove(&auction_id);
+
+		// priced before the token moves, a winning bid that no longer covers a fee raised since
+		// creation is refunded and the auction closes as if nobody had bid
+		let winner = auction.highest_bid.clone()
+			.map(|bid| (self.internal_fee_for(bid.amount.0, auction.is_holder), bid));
+
+		let (fee, bid) = match winner {
+			Some((fee, bid)) if bid.amount.0 >= fee => (fee, bid),
+			outpriced => {
+				if let Some((_, bid)) = outpriced {
+					Promise::new(bid.bidder_id).transfer(NearToken::from_yoctonear(bid.amount.0));
+				}
+
+				self.internal_return_auction_token(&auction);
+
+				emit_event(EventLogVariant::AuctionSettled(vec![AuctionSettledLog {
+					auction_id,
+					seller_id: auction.seller_id,
+					winner_id: None,
+					contract_id: auction.token.contract_id,
+					token_id: auction.token.token_id,
+					price: U128(0),
+					fee: None,
+				}]));
+
+				return PromiseOrValue::Value(false)
+			},
+		};
+
+		PromiseOrValue::Promise(nft_transfer(&auction.token, &bid.bidder_id)
+			.then(
+				Self::ext(env::current_account_id()).with_static_gas(GAS_FOR_RESOLVE_AUCTION)
+				.resolve_auction(auction, U128(fee))
+			))
+	}
+
+	// pays the seller once the NFT reached the winner, otherwise refunds the winner and
+	// reopens the ended auction without bids so the next settle_auction returns the token
+	#[private]
+	pub fn resolve_auction(
+		&mut self,
+		auction: Auction,
+		fee: U128,
+	) -> bool {
+		let bid = auction.highest_bid.clone().expect("Auction has no bid");
+
+		if !matches!(env::promise_result(0), PromiseResult::Successful(_)) {
+			Promise::new(bid.bidder_id).transfer(NearToken::from_yoctonear(bid.amount.0));
+			self.auctions.insert(&auction.id, &Auction { highest_bid: None, ..auction.clone() });
+
+			env::log_str(format!("Transfer for auction {} failed, winner refunded", auction.id).as_str());
+			return false
+		}
+
+		self.internal_release_token(&auction.token);
+
+		let price = bid.amount.0;
+		let fee = fee.0;
+
+		Promise::new(self.fee_wallet.clone()).transfer(NearToken::from_yoctonear(fee));
+		Promise::new(auction.seller_id.clone()).transfer(NearToken::from_yoctonear(price - fee));
+
+		self.internal_record_sale(&auction.seller_id, &bid.bidder_id, &auction.token, price, fee);
+
+		emit_event(EventLogVariant::AuctionSettled(vec![AuctionSettledLog {
+			auction_id: auction.id,
+			seller_id: auction.seller_id,
+			winner_id: Some(bid.bidder_id),
+			contract_id: auction.token.contract_id,
+			token_id: auction.token.token_id,
+			price: bid.amount,
+			fee: Some(NearTransferLog { receiver_id: self.fee_wallet.clone(), amount: U128(fee) }),
+		}]));
+
+		true
+	}
+
+	// only before the first bid, afterwards the auction has to run out
+	#[payable]
+	pub fn cancel_auction(
+		&mut self,
+		auction_id: u64,
+	) {
+		assert_one_yocto();
+
+		let auction = self.auctions.get(&auction_id).expect("Auction not found");
+
+		assert_eq!(
+			env::predecessor_account_id(),
+			auction.seller_id,
+			"Caller is not the seller"
+		);
+
+		assert!(
+			auction.highest_bid.is_none(),
+			"Auction already has bids"
+		);
+
+		self.auctions.remove(&auction_id);
+		self.internal_return_auction_token(&auction);
+
+		emit_event(EventLogVariant::AuctionCancelled(vec![AuctionCancelledLog {
+			auction_id,
+			seller_id: auction.seller_id,
+			contract_id: auction.token.contract_id,
+			token_id: auction.token.token_id,
+		}]));
+	}
+}
+
+impl Contract {
+	// escrows a token sent with an auction msg, the auction starts once the seller's holder status is known
+	pub(crate) fn internal_deposit_auction(
+		&mut self,
+		seller_id: AccountId,
+		token: TokenData,
+		reserve_price: U128,
+		min_increment: U128,
+		duration: U64,
+	) -> PromiseOrValue<bool> {
+		assert!(
+			reserve_price.0 >= self.required_cost.0,
+			"Reserve price must cover the {} yoctoNEAR fee",
+			self.required_cost.0
+		);
+
+		assert!(
+			min_increment.0 > 0,
+			"Minimum increment can't be 0"
+		);
+
+		assert!(
+			duration.0 >= ANTI_SNIPING_WINDOW,
+			"Auction must run for at least {} nanoseconds",
+			ANTI_SNIPING_WINDOW
+		);
+
+		assert!(
+			duration.0 <= MAX_AUCTION_DURATION,
+			"Auction can run for at most {} nanoseconds",
+			MAX_AUCTION_DURATION
+		);
+
+		let auction_id = self.next_auction_id;
+		self.next_auction_id += 1;
+
+		self.internal_escrow_token(&seller_id, &token, EscrowHolder::Auction(auction_id));
+
+		let promise = ext_nft_contract::ext(self.holder_collection.clone()).nft_tokens_for_owner(seller_id.clone(), "0".to_string(), 1);
+
+		PromiseOrValue::Promise(promise.then(
+			Self::ext(env::current_account_id()).with_static_gas(GAS_FOR_AUCTION_CALLBACK)
+			.callback_create_auction(auction_id, seller_id, token, reserve_price, min_increment, duration)
+		))
+	}
+
+	fn internal_return_auction_token(&mut self, auction: &Auction) {
+		self.internal_return_token(&auction.token);
+	}
+}
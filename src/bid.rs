@@ -0,0 +1,235 @@
+use crate::*;
+use near_sdk::{assert_one_yocto, Gas, Promise, PromiseOrValue, PromiseResult};
+
+const GAS_FOR_RESOLVE_BID: Gas = Gas::from_tgas(20);
+
+// NEAR escrowed against a specific NFT, whoever holds it can accept with nft_transfer_call
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub struct TokenBid {
+	pub id: u64,
+	pub bidder_id: AccountId,
+	pub contract_id: AccountId,
+	pub token_id: TokenId,
+	pub amount: U128,
+	pub created_at: u64,
+}
+
+#[near_bindgen]
+impl Contract {
+
+	pub fn get_bid(&self, bid_id: u64) -> Option<TokenBid> {
+		self.bids.get(&bid_id)
+	}
+
+	pub fn get_bids_for_token(&self, contract_id: AccountId, token_id: TokenId) -> Vec<TokenBid> {
+		self.bids_per_token
+			.get(&(contract_id, token_id))
+			.unwrap_or_default()
+			.iter()
+			.filter_map(|id| self.bids.get(id))
+			.collect()
+	}
+
+	pub fn get_bids_for_account(&self, account_id: AccountId) -> Vec<TokenBid> {
+		self.bids_per_account
+			.get(&account_id)
+			.unwrap_or_default()
+			.iter()
+			.filter_map(|id| self.bids.get(id))
+			.collect()
+	}
+
+	// the attached deposit is the bid
+	#[payable]
+	pub fn create_bid(
+		&mut self,
+		contract_id: AccountId,
+		token_id: TokenId,
+	) -> u64 {
+		self.assert_offers_not_paused();
+
+		let bidder_id = env::predecessor_account_id();
+		let amount = env::attached_deposit().as_yoctonear();
+
+		self.assert_not_blocked(&bidder_id);
+		self.assert_collection_allowed(&contract_id);
+
+		assert!(
+			amount >= self.required_cost.0,
+			"Bid must cover the {} yoctoNEAR fee",
+			self.required_cost.0
+		);
+
+		let bid_id = self.next_bid_id;
+		self.next_bid_id += 1;
+
+		let bid = TokenBid {
+			id: bid_id,
+			bidder_id,
+			contract_id,
+			token_id,
+			amount: U128(amount),
+			created_at: env::block_timestamp(),
+		};
+
+		self.internal_add_bid(&bid);
+
+		emit_event(EventLogVariant::BidCreated(vec![BidCreatedLog {
+			bid_id,
+			bidder_id: bid.bidder_id,
+			contract_id: bid.contract_id,
+			token_id: bid.token_id,
+			amount: bid.amount,
+		}]));
+
+		bid_id
+	}
+
+	#[payable]
+	pub fn withdraw_bid(
+		&mut self,
+		bid_id: u64,
+	) {
+		assert_one_yocto();
+
+		let bid = self.bids.get(&bid_id).expect("Bid not found");
+
+		assert_eq!(
+			env::predecessor_account_id(),
+			bid.bidder_id,
+			"Caller is not the bidder"
+		);
+
+		self.internal_remove_bid(&bid);
+		Promise::new(bid.bidder_id.clone()).transfer(NearToken::from_yoctonear(bid.amount.0));
+
+		emit_event(EventLogVariant::BidWithdrawn(vec![BidWithdrawnLog {
+			bid_id,
+			bidder_id: bid.bidder_id,
+			amount: bid.amount,
+		}]));
+	}
+
+	// pays the seller once the NFT reached the bidder, otherwise the bid is restored and
+	// returning true makes the NFT contract send the token back to the seller
+	#[private]
+	pub fn resolve_bid(
+		&mut self,
+		bid: TokenBid,
+		seller_id: AccountId,
+		fee: U128,
+	) -> bool {
+		let token = TokenData { contract_id: bid.contract_id.clone(), token_id: bid.token_id.clone() };
+		self.internal_release_token(&token);
+
+		if !matches!(env::promise_result(0), PromiseResult::Successful(_)) {
+			self.internal_add_bid(&bid);
+
+			env::log_str(format!("Transfer for bid {} failed, returning token", bid.id).as_str());
+			return true
+		}
+
+		let amount = bid.amount.0;
+		let fee = fee.0;
+
+		Promise::new(self.fee_wallet.clone()).transfer(NearToken::from_yoctonear(fee));
+		Promise::new(seller_id.clone()).transfer(NearToken::from_yoctonear(amount - fee));
+
+		self.internal_record_sale(&seller_id, &bid.bidder_id, &token, amount, fee);
+
+		emit_event(EventLogVariant::BidAccepted(vec![BidAcceptedLog {
+			bid_id: bid.id,
+			bidder_id: bid.bidder_id,
+			seller_id,
+			contract_id: bid.contract_id,
+			token_id: bid.token_id,
+			amount: bid.amount,
+			fee: NearTransferLog { receiver_id: self.fee_wallet.clone(), amount: U128(fee) },
+		}]));
+
+		false
+	}
+}
+
+impl Contract {
+	// a holder accepting a bid through nft_transfer_call, the token is forwarded to the bidder straight away.
+	// there is no holder lookup on this path so sellers pay the standard fee
+	pub(crate) fn internal_accept_bid(&mut self, seller_id: AccountId, token: TokenData, bid_id: u64) -> PromiseOrValue<bool> {
+		// accepting settles straight away, so hand the token back while settlement is paused
+		if self.pause.settlement {
+			env::log_str("settlement is paused, returning token");
+			return PromiseOrValue::Value(true)
+		}
+
+		let bid = self.bids.get(&bid_id).expect("Bid not found");
+
+		assert!(
+			bid.contract_id == token.contract_id && bid.token_id == token.token_id,
+			"Bid is for another token"
+		);
+
+		assert_ne!(
+			seller_id,
+			bid.bidder_id,
+			"Can't accept your own bid"
+		);
+
+		// priced before the token moves, a fee raised since the bid was placed can't leave the callback short
+		let fee = self.internal_fee_for(bid.amount.0, false);
+
+		assert!(
+			bid.amount.0 >= fee,
+			"Bid no longer covers the {} yoctoNEAR fee",
+			fee
+		);
+
+		self.internal_remove_bid(&bid);
+		self.internal_escrow_token(&seller_id, &token, EscrowHolder::Bid(bid_id));
+
+		PromiseOrValue::Promise(nft_transfer(&token, &bid.bidder_id)
+			.then(
+				Self::ext(env::current_account_id()).with_static_gas(GAS_FOR_RESOLVE_BID)
+				.resolve_bid(bid, seller_id, U128(fee))
+			))
+	}
+
+	fn internal_add_bid(&mut self, bid: &TokenBid) {
+		self.bids.insert(&bid.id, bid);
+
+		let token_key = (bid.contract_id.clone(), bid.token_id.clone());
+		let mut token_bids = self.bids_per_token.get(&token_key).unwrap_or_default();
+		token_bids.push(bid.id);
+		self.bids_per_token.insert(&token_key, &token_bids);
+
+		let mut account_bids = self.bids_per_account.get(&bid.bidder_id).unwrap_or_default();
+		account_bids.push(bid.id);
+		self.bids_per_account.insert(&bid.bidder_id, &account_bids);
+	}
+
+	fn internal_remove_bid(&mut self, bid: &TokenBid) {
+		self.bids.remove(&bid.id);
+
+		let token_key = (bid.contract_id.clone(), bid.token_id.clone());
+		if let Some(mut token_bids) = self.bids_per_token.get(&token_key) {
+			token_bids.retain(|x| *x != bid.id);
+
+			if token_bids.is_empty() {
+				self.bids_per_token.remove(&token_key);
+			} else {
+				self.bids_per_token.insert(&token_key, &token_bids);
+			}
+		}
+
+		if let Some(mut account_bids) = self.bids_per_account.get(&bid.bidder_id) {
+			account_bids.retain(|x| *x != bid.id);
+
+			if account_bids.is_empty() {
+				self.bids_per_account.remove(&bid.bidder_id);
+			} else {
+				self.bids_per_account.insert(&bid.bidder_id, &account_bids);
+			}
+		}
+	}
+}
@@ -0,0 +1,82 @@
+use crate::*;
+use near_sdk::assert_one_yocto;
+
+// Bound requires every new offer hash to commit to its terms
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub enum HashMode {
+	Free,
+	Bound,
+}
+
+// canonical offer payload, a bound hash is hex(sha256(borsh(terms)))
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub struct OfferTerms {
+	pub sender_id: AccountId,
+	pub sender_near: U128,
+	pub sender_nfts: Vec<TokenData>,
+	pub receiver_id: AccountId,
+	pub receiver_nfts: Vec<TokenData>,
+}
+
+impl OfferTerms {
+	pub fn hash(&self) -> Hash {
+		env::sha256(&borsh::to_vec(self).unwrap())
+			.iter()
+			.map(|byte| format!("{:02x}", byte))
+			.collect()
+	}
+}
+
+impl From<&HashOffer> for OfferTerms {
+	fn from(offer: &HashOffer) -> Self {
+		OfferTerms {
+			sender_id: offer.sender_id.clone(),
+			sender_near: U128(offer.sender_near),
+			sender_nfts: offer.sender_nfts.clone(),
+			receiver_id: offer.receiver_id.clone(),
+			receiver_nfts: offer.receiver_nfts.clone(),
+		}
+	}
+}
+
+#[near_bindgen]
+impl Contract {
+
+	pub fn get_hash_mode(&self) -> HashMode {
+		self.hash_mode
+	}
+
+	// only applies to offers created afterwards
+	#[payable]
+	pub fn set_hash_mode(&mut self, hash_mode: HashMode) {
+		assert_one_yocto();
+		self.assert_owner();
+
+		env::log_str(format!("Hash mode: {:?}", hash_mode).as_str());
+		self.hash_mode = hash_mode;
+	}
+
+	pub fn compute_offer_hash(&self, terms: OfferTerms) -> Hash {
+		terms.hash()
+	}
+
+	// recomputes the binding of a stored offer, None if the offer isn't open
+	pub fn verify_offer_hash(&self, hash: Hash) -> Option<bool> {
+		self.hash_map.get(&hash).map(|offer| OfferTerms::from(&offer).hash() == hash)
+	}
+}
+
+impl Contract {
+	pub(crate) fn assert_hash_binding(&self, hash: &Hash, terms: &OfferTerms) {
+		if self.hash_mode == HashMode::Bound {
+			assert!(
+				&terms.hash() == hash,
+				"Hash does not match the offer terms"
+			);
+		}
+	}
+}
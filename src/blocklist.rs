@@ -0,0 +1,53 @@
+use crate::*;
+use near_sdk::assert_one_yocto;
+
+#[near_bindgen]
+impl Contract {
+
+	pub fn is_blocked(&self, account_id: AccountId) -> bool {
+		self.blocked_accounts.contains(&account_id)
+	}
+
+	pub fn get_blocked_accounts(&self, from_index: Option<U128>, limit: Option<u64>) -> Vec<AccountId> {
+		let start = u128::from(from_index.unwrap_or(U128(0)));
+
+		self.blocked_accounts
+			.iter()
+			.skip(start as usize)
+			.take(limit.unwrap_or(50) as usize)
+			.collect()
+	}
+
+	// blocked accounts can still cancel their existing offers and recover escrow
+	#[payable]
+	pub fn block_accounts(&mut self, account_ids: Vec<AccountId>) {
+		assert_one_yocto();
+		self.assert_role(Role::Moderator);
+
+		for account_id in account_ids.iter() {
+			self.blocked_accounts.insert(account_id);
+			env::log_str(format!("Blocked account: {}", account_id).as_str());
+		}
+	}
+
+	#[payable]
+	pub fn unblock_accounts(&mut self, account_ids: Vec<AccountId>) {
+		assert_one_yocto();
+		self.assert_role(Role::Moderator);
+
+		for account_id in account_ids.iter() {
+			self.blocked_accounts.remove(account_id);
+			env::log_str(format!("Unblocked account: {}", account_id).as_str());
+		}
+	}
+}
+
+impl Contract {
+	pub(crate) fn assert_not_blocked(&self, account_id: &AccountId) {
+		assert!(
+			!self.blocked_accounts.contains(account_id),
+			"Account {} is blocked",
+			account_id
+		);
+	}
+}
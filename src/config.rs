@@ -0,0 +1,48 @@
+use crate::*;
+
+// upper bound on sender_nfts + receiver_nfts in a single offer
+pub const MAX_NFTS_PER_OFFER: usize = 8;
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ContractConfig {
+	pub version: String,
+	pub owner_id: AccountId,
+	pub pending_owner: Option<AccountId>,
+	pub fee_wallet: AccountId,
+	pub required_cost: U128,
+	pub percentage_fee_threshold: U128,
+	pub percentage_fee_bps: u32,
+	pub holder_collection: AccountId,
+	pub max_nfts_per_offer: u32,
+	pub offer_expiry: U64,
+	pub timelock_delay: U64,
+	pub archive_retention: Option<u64>,
+	pub collection_mode: CollectionMode,
+	pub hash_mode: HashMode,
+	pub pause: PauseState,
+}
+
+#[near_bindgen]
+impl Contract {
+
+	pub fn get_config(&self) -> ContractConfig {
+		ContractConfig {
+			version: env!("CARGO_PKG_VERSION").to_string(),
+			owner_id: self.owner_id.clone(),
+			pending_owner: self.pending_owner.clone(),
+			fee_wallet: self.fee_wallet.clone(),
+			required_cost: self.required_cost,
+			percentage_fee_threshold: self.percentage_fee_threshold,
+			percentage_fee_bps: self.percentage_fee_bps,
+			holder_collection: self.holder_collection.clone(),
+			max_nfts_per_offer: MAX_NFTS_PER_OFFER as u32,
+			offer_expiry: U64(self.offer_expiry),
+			timelock_delay: U64(self.timelock_delay),
+			archive_retention: self.archive_retention,
+			collection_mode: self.collection_mode,
+			hash_mode: self.hash_mode,
+			pause: self.pause.clone(),
+		}
+	}
+}
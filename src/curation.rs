@@ -0,0 +1,109 @@
+use crate::*;
+use near_sdk::assert_one_yocto;
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub enum CollectionMode {
+	// any NFT contract is accepted
+	Open,
+	// only allowlisted NFT contracts are accepted
+	AllowlistOnly,
+	// any NFT contract except the denylisted ones is accepted
+	Denylist,
+}
+
+#[near_bindgen]
+impl Contract {
+
+	pub fn get_collection_mode(&self) -> CollectionMode {
+		self.collection_mode
+	}
+
+	pub fn get_allowed_collections(&self, from_index: Option<U128>, limit: Option<u64>) -> Vec<AccountId> {
+		let start = u128::from(from_index.unwrap_or(U128(0)));
+
+		self.allowed_collections
+			.iter()
+			.skip(start as usize)
+			.take(limit.unwrap_or(50) as usize)
+			.collect()
+	}
+
+	pub fn get_denied_collections(&self, from_index: Option<U128>, limit: Option<u64>) -> Vec<AccountId> {
+		let start = u128::from(from_index.unwrap_or(U128(0)));
+
+		self.denied_collections
+			.iter()
+			.skip(start as usize)
+			.take(limit.unwrap_or(50) as usize)
+			.collect()
+	}
+
+	pub fn is_collection_allowed(&self, contract_id: AccountId) -> bool {
+		match self.collection_mode {
+			CollectionMode::Open => true,
+			CollectionMode::AllowlistOnly => self.allowed_collections.contains(&contract_id),
+			CollectionMode::Denylist => !self.denied_collections.contains(&contract_id),
+		}
+	}
+
+	#[payable]
+	pub fn set_collection_mode(&mut self, mode: CollectionMode) {
+		assert_one_yocto();
+		self.assert_role(Role::Curator);
+
+		env::log_str(format!("Collection mode: {:?}", mode).as_str());
+		self.collection_mode = mode;
+	}
+
+	#[payable]
+	pub fn allow_collections(&mut self, contract_ids: Vec<AccountId>) {
+		assert_one_yocto();
+		self.assert_role(Role::Curator);
+
+		for contract_id in contract_ids.iter() {
+			self.allowed_collections.insert(contract_id);
+		}
+	}
+
+	#[payable]
+	pub fn remove_allowed_collections(&mut self, contract_ids: Vec<AccountId>) {
+		assert_one_yocto();
+		self.assert_role(Role::Curator);
+
+		for contract_id in contract_ids.iter() {
+			self.allowed_collections.remove(contract_id);
+		}
+	}
+
+	#[payable]
+	pub fn deny_collections(&mut self, contract_ids: Vec<AccountId>) {
+		assert_one_yocto();
+		self.assert_role(Role::Curator);
+
+		for contract_id in contract_ids.iter() {
+			self.denied_collections.insert(contract_id);
+		}
+	}
+
+	#[payable]
+	pub fn remove_denied_collections(&mut self, contract_ids: Vec<AccountId>) {
+		assert_one_yocto();
+		self.assert_role(Role::Curator);
+
+		for contract_id in contract_ids.iter() {
+			self.denied_collections.remove(contract_id);
+		}
+	}
+}
+
+impl Contract {
+	pub(crate) fn assert_collection_allowed(&self, contract_id: &AccountId) {
+		assert!(
+			self.is_collection_allowed(contract_id.clone()),
+			"Collection {} is not allowed",
+			contract_id
+		);
+	}
+}
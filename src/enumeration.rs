@@ -0,0 +1,22 @@
+use crate::*;
+
+#[near_bindgen]
+impl Contract {
+
+	// paginate through every open offer. removals swap the last offer into the freed slot,
+	// so the order is stable only while no offer closes
+	pub fn get_offers(&self, from_index: Option<U128>, limit: Option<u64>) -> Vec<(Hash, HashOffer)> {
+		let hashes = self.open_offers.as_vector();
+		let start = u128::from(from_index.unwrap_or(U128(0))).min(hashes.len() as u128) as u64;
+		let end = hashes.len().min(start + limit.unwrap_or(50));
+
+		(start..end)
+			.filter_map(|index| hashes.get(index))
+			.filter_map(|hash| self.hash_map.get(&hash).map(|offer| (hash, offer)))
+			.collect()
+	}
+
+	pub fn total_open_offers(&self) -> U128 {
+		U128(self.open_offers.len() as u128)
+	}
+}
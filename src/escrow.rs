@@ -0,0 +1,126 @@
+use crate::*;
+use near_sdk::{Gas, PromiseResult};
+
+const GAS_FOR_RESOLVE_RETURN: Gas = Gas::from_tgas(10);
+
+// (contract_id, token_id) uniquely identifies an NFT across collections
+pub type EscrowKey = (AccountId, TokenId);
+
+// what the escrowed token is locked into
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub enum EscrowHolder {
+	Offer(Hash),
+	Listing(u64),
+	Auction(u64),
+	// held only while the token is forwarded to the bidder
+	Bid(u64),
+	// on its way back to the depositor, the record is dropped once the transfer went through
+	Returning,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub struct EscrowedToken {
+	pub depositor_id: AccountId,
+	pub contract_id: AccountId,
+	pub token_id: TokenId,
+	pub holder: EscrowHolder,
+	pub deposited_at: u64,
+}
+
+#[near_bindgen]
+impl Contract {
+
+	// reports which offer, listing, auction or bid currently holds the token, if any
+	pub fn get_escrowed_token(&self, contract_id: AccountId, token_id: TokenId) -> Option<EscrowedToken> {
+		self.escrowed_tokens.get(&(contract_id, token_id))
+	}
+
+	// callable by anyone, re-sends a token whose return to the depositor failed
+	pub fn retry_token_return(&mut self, contract_id: AccountId, token_id: TokenId) {
+		let escrowed = self.escrowed_tokens.get(&(contract_id.clone(), token_id.clone())).expect("Token not escrowed");
+
+		assert!(
+			escrowed.holder == EscrowHolder::Returning,
+			"Token is not being returned"
+		);
+
+		self.internal_return_token(&TokenData { contract_id, token_id });
+	}
+
+	// drops the escrow record once the token is back with its depositor, a failed transfer keeps it for `retry_token_return`
+	#[private]
+	pub fn resolve_token_return(&mut self, token: TokenData) {
+		let returning = self.escrowed_tokens
+			.get(&(token.contract_id.clone(), token.token_id.clone()))
+			.is_some_and(|escrowed| escrowed.holder == EscrowHolder::Returning);
+
+		if !returning {
+			return
+		}
+
+		if matches!(env::promise_result(0), PromiseResult::Successful(_)) {
+			self.internal_release_token(&token);
+		} else {
+			env::log_str(format!("Return of {} {} failed, retry with retry_token_return", token.contract_id, token.token_id).as_str());
+		}
+	}
+}
+
+impl Contract {
+	pub(crate) fn internal_escrow_token(&mut self, depositor_id: &AccountId, token: &TokenData, holder: EscrowHolder) {
+		let key = (token.contract_id.clone(), token.token_id.clone());
+
+		assert!(
+			!self.escrowed_tokens.contains_key(&key),
+			"Token already escrowed"
+		);
+
+		self.escrowed_tokens.insert(&key, &EscrowedToken {
+			depositor_id: depositor_id.clone(),
+			contract_id: token.contract_id.clone(),
+			token_id: token.token_id.clone(),
+			holder,
+			deposited_at: env::block_timestamp(),
+		});
+
+		let mut tokens_per_owner_vec = self.tokens_per_owner.get(depositor_id).unwrap_or_default();
+		tokens_per_owner_vec.push(token.clone());
+		self.tokens_per_owner.insert(depositor_id, &tokens_per_owner_vec);
+	}
+
+	// sends the token back to its depositor, the record stays until `resolve_token_return` sees the transfer succeed
+	pub(crate) fn internal_return_token(&mut self, token: &TokenData) {
+		let key = (token.contract_id.clone(), token.token_id.clone());
+		let mut escrowed = self.escrowed_tokens.get(&key).expect("Token not escrowed");
+
+		escrowed.holder = EscrowHolder::Returning;
+		self.escrowed_tokens.insert(&key, &escrowed);
+
+		nft_transfer(token, &escrowed.depositor_id).then(
+			Self::ext(env::current_account_id()).with_static_gas(GAS_FOR_RESOLVE_RETURN)
+			.resolve_token_return(token.clone())
+		);
+	}
+
+	// drops the escrow record and the depositor's index entry for the token
+	pub(crate) fn internal_release_token(&mut self, token: &TokenData) -> Option<EscrowedToken> {
+		let escrowed = self.escrowed_tokens.remove(&(token.contract_id.clone(), token.token_id.clone()))?;
+
+		if let Some(mut tokens_arr) = self.tokens_per_owner.get(&escrowed.depositor_id) {
+			tokens_arr.retain(|x| !(x.contract_id == token.contract_id && x.token_id == token.token_id));
+
+			// remove token from storage
+			if tokens_arr.is_empty() {
+				self.tokens_per_owner.remove(&escrowed.depositor_id);
+			} else {
+				self.tokens_per_owner.insert(&escrowed.depositor_id, &tokens_arr);
+			}
+		}
+
+		Some(escrowed)
+	}
+}
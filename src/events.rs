@@ -0,0 +1,253 @@
+use crate::*;
+use std::fmt;
+
+// NEP-297 event standard, logged as `EVENT_JSON:{...}`
+pub const EVENT_STANDARD: &str = "genadropswap";
+pub const EVENT_STANDARD_VERSION: &str = "1.0.0";
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(tag = "event", content = "data")]
+#[serde(rename_all = "snake_case")]
+pub enum EventLogVariant {
+	OfferCreated(Vec<OfferCreatedLog>),
+	NftDeposited(Vec<NftDepositedLog>),
+	OfferFunded(Vec<OfferFundedLog>),
+	SwapSettled(Vec<SwapSettledLog>),
+	OfferCancelled(Vec<OfferCancelledLog>),
+	OfferExpired(Vec<OfferExpiredLog>),
+	OfferAmended(Vec<OfferAmendedLog>),
+	ListingCreated(Vec<ListingCreatedLog>),
+	ListingSold(Vec<ListingSoldLog>),
+	ListingCancelled(Vec<ListingCancelledLog>),
+	AuctionCreated(Vec<AuctionCreatedLog>),
+	AuctionBid(Vec<AuctionBidLog>),
+	AuctionSettled(Vec<AuctionSettledLog>),
+	AuctionCancelled(Vec<AuctionCancelledLog>),
+	BidCreated(Vec<BidCreatedLog>),
+	BidWithdrawn(Vec<BidWithdrawnLog>),
+	BidAccepted(Vec<BidAcceptedLog>),
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct EventLog {
+	pub standard: String,
+	pub version: String,
+
+	// `flatten` to not have "event": {<EventLogVariant>} in the JSON, just have the contents of {<EventLogVariant>}.
+	#[serde(flatten)]
+	pub event: EventLogVariant,
+}
+
+impl fmt::Display for EventLog {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_fmt(format_args!(
+			"EVENT_JSON:{}",
+			&near_sdk::serde_json::to_string(self).map_err(|_| fmt::Error)?
+		))
+	}
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct OfferCreatedLog {
+	pub hash: Hash,
+	pub sender_id: AccountId,
+	pub sender_near: U128,
+	pub sender_nfts: Vec<TokenData>,
+	pub receiver_id: AccountId,
+	pub receiver_nfts: Vec<TokenData>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct NftDepositedLog {
+	pub hash: Hash,
+	pub owner_id: AccountId,
+	pub contract_id: AccountId,
+	pub token_id: TokenId,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct OfferFundedLog {
+	pub hash: Hash,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct NftTransferLog {
+	pub contract_id: AccountId,
+	pub token_id: TokenId,
+	pub old_owner_id: AccountId,
+	pub new_owner_id: AccountId,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct NearTransferLog {
+	pub receiver_id: AccountId,
+	pub amount: U128,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SwapSettledLog {
+	pub hash: Hash,
+	pub nft_transfers: Vec<NftTransferLog>,
+	pub near_transfers: Vec<NearTransferLog>,
+	pub fee: NearTransferLog,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct OfferCancelledLog {
+	pub hash: Hash,
+	pub cancelled_by: AccountId,
+	pub returned_nfts: Vec<TokenData>,
+	pub refunded_near: U128,
+
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub reason: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct OfferExpiredLog {
+	pub hash: Hash,
+	pub returned_nfts: Vec<TokenData>,
+	pub refunded_near: U128,
+}
+
+// receiver_id is included so the receiver can pick up changed terms from the log
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct OfferAmendedLog {
+	pub hash: Hash,
+	pub sender_id: AccountId,
+	pub receiver_id: AccountId,
+	pub sender_near: U128,
+	pub sender_nfts: Vec<TokenData>,
+	pub returned_nfts: Vec<TokenData>,
+	pub refunded_near: U128,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ListingCreatedLog {
+	pub listing_id: u64,
+	pub seller_id: AccountId,
+	pub contract_id: AccountId,
+	pub token_id: TokenId,
+	pub pricing: ListingPrice,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ListingSoldLog {
+	pub listing_id: u64,
+	pub seller_id: AccountId,
+	pub buyer_id: AccountId,
+	pub contract_id: AccountId,
+	pub token_id: TokenId,
+	pub price: U128,
+	pub fee: NearTransferLog,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ListingCancelledLog {
+	pub listing_id: u64,
+	pub seller_id: AccountId,
+	pub contract_id: AccountId,
+	pub token_id: TokenId,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct AuctionCreatedLog {
+	pub auction_id: u64,
+	pub seller_id: AccountId,
+	pub contract_id: AccountId,
+	pub token_id: TokenId,
+	pub reserve_price: U128,
+	pub min_increment: U128,
+	pub ends_at: U64,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct AuctionBidLog {
+	pub auction_id: u64,
+	pub bidder_id: AccountId,
+	pub amount: U128,
+	// moves when a late bid triggers the anti-sniping extension
+	pub ends_at: U64,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct AuctionSettledLog {
+	pub auction_id: u64,
+	pub seller_id: AccountId,
+
+	// none when the auction closed without a winning bid
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub winner_id: Option<AccountId>,
+	pub contract_id: AccountId,
+	pub token_id: TokenId,
+	pub price: U128,
+
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub fee: Option<NearTransferLog>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct AuctionCancelledLog {
+	pub auction_id: u64,
+	pub seller_id: AccountId,
+	pub contract_id: AccountId,
+	pub token_id: TokenId,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct BidCreatedLog {
+	pub bid_id: u64,
+	pub bidder_id: AccountId,
+	pub contract_id: AccountId,
+	pub token_id: TokenId,
+	pub amount: U128,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct BidWithdrawnLog {
+	pub bid_id: u64,
+	pub bidder_id: AccountId,
+	pub amount: U128,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct BidAcceptedLog {
+	pub bid_id: u64,
+	pub bidder_id: AccountId,
+	pub seller_id: AccountId,
+	pub contract_id: AccountId,
+	pub token_id: TokenId,
+	pub amount: U128,
+	pub fee: NearTransferLog,
+}
+
+pub(crate) fn emit_event(event: EventLogVariant) {
+	let log = EventLog {
+		standard: EVENT_STANDARD.to_string(),
+		version: EVENT_STANDARD_VERSION.to_string(),
+		event,
+	};
+
+	env::log_str(&log.to_string());
+}
@@ -0,0 +1,69 @@
+use crate::*;
+use near_sdk::assert_one_yocto;
+
+#[near_bindgen]
+impl Contract {
+
+	// nanoseconds an offer stays open before anyone can expire it, 0 disables expiry.
+	// only applies to offers created afterwards
+	#[payable]
+	pub fn set_offer_expiry(&mut self, offer_expiry: U64) {
+		assert_one_yocto();
+		self.assert_owner();
+
+		env::log_str(format!("Offer expiry: {}", offer_expiry.0).as_str());
+		self.offer_expiry = offer_expiry.0;
+	}
+
+	pub fn is_offer_expired(&self, hash: Hash) -> bool {
+		self.hash_map.get(&hash).map(|offer| self.internal_is_expired(&offer)).unwrap_or(false)
+	}
+
+	// callable by anyone once an unfunded offer outlived its expiry, unwinds it like a cancellation.
+	// funded offers only wait on settlement and can't be expired
+	pub fn expire_offer(
+		&mut self,
+		hash: Hash,
+	) {
+		let mut hash_transaction = self.hash_map.get(&hash).expect("Hash not found!");
+
+		assert!(
+			self.internal_is_expired(&hash_transaction),
+			"Offer has not expired"
+		);
+
+		self.internal_set_status(&mut hash_transaction, OfferStatus::Expired);
+
+		let returned_nfts = self.internal_return_escrow(&hash_transaction);
+		let refunded_near = self.internal_refund_closed_offer(&hash_transaction);
+
+		self.internal_delete_offer(&hash, &hash_transaction);
+		self.internal_archive_offer(&hash, &hash_transaction, OfferOutcome::Expired, hash_transaction.cancel_fee());
+
+		emit_event(EventLogVariant::OfferExpired(vec![OfferExpiredLog {
+			hash,
+			returned_nfts,
+			refunded_near: U128(refunded_near),
+		}]));
+	}
+}
+
+impl Contract {
+	// stored on new offers, so changing the expiry never reaches offers that are already open
+	pub(crate) fn internal_expiry_deadline(&self) -> u64 {
+		if self.offer_expiry == 0 {
+			0
+		} else {
+			env::block_timestamp() + self.offer_expiry
+		}
+	}
+
+	// deposits, amendments and settlement stop at the deadline even before anyone calls `expire_offer`
+	pub(crate) fn internal_is_past_deadline(&self, offer: &HashOffer) -> bool {
+		offer.expires_at > 0 && env::block_timestamp() >= offer.expires_at
+	}
+
+	pub(crate) fn internal_is_expired(&self, offer: &HashOffer) -> bool {
+		offer.status.can_transition_to(OfferStatus::Expired) && self.internal_is_past_deadline(offer)
+	}
+}
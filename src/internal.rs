@@ -0,0 +1,177 @@
+use crate::*;
+use near_sdk::{Gas, Promise};
+
+// offers of at least 10N pay a 1% fee instead of the base fee unless changed through the timelock
+pub(crate) const DEFAULT_PERCENTAGE_FEE_THRESHOLD: u128 = 10_000_000_000_000_000_000_000_000;
+pub(crate) const DEFAULT_PERCENTAGE_FEE_BPS: u32 = 100;
+
+const GAS_FOR_RESOLVE_SETTLEMENT: Gas = Gas::from_tgas(50);
+
+// distinct NFT contracts in the order they first appear
+pub(crate) fn collections_of<'a>(tokens: impl Iterator<Item = &'a TokenData>) -> Vec<AccountId> {
+	let mut collections: Vec<AccountId> = Vec::new();
+
+	for token in tokens {
+		if !collections.contains(&token.contract_id) {
+			collections.push(token.contract_id.clone());
+		}
+	}
+
+	collections
+}
+
+impl Contract {
+	// stores a new offer and indexes it for both parties and the global open offer set
+	pub(crate) fn internal_add_offer(&mut self, hash: &Hash, offer: &HashOffer) {
+		self.hash_map.insert(hash, offer);
+
+		let mut hash_set = self.hashes_per_owner.get(&offer.sender_id).unwrap_or_default();
+		hash_set.push(hash.clone());
+		self.hashes_per_owner.insert(&offer.sender_id, &hash_set);
+
+		let mut receiver_hash_set = self.hashes_per_owner.get(&offer.receiver_id).unwrap_or_default();
+		receiver_hash_set.push(hash.clone());
+		self.hashes_per_owner.insert(&offer.receiver_id, &receiver_hash_set);
+
+		self.open_offers.insert(hash);
+		self.internal_count_status(offer.status);
+
+		emit_event(EventLogVariant::OfferCreated(vec![OfferCreatedLog {
+			hash: hash.clone(),
+			sender_id: offer.sender_id.clone(),
+			sender_near: U128(offer.sender_near),
+			sender_nfts: offer.sender_nfts.clone(),
+			receiver_id: offer.receiver_id.clone(),
+			receiver_nfts: offer.receiver_nfts.clone(),
+		}]));
+	}
+
+	// fee owed on `sender_near`; holders always pay the base fee
+	pub(crate) fn internal_fee_for(&self, sender_near: u128, is_holder: bool) -> u128 {
+		if sender_near >= self.percentage_fee_threshold.0 && !is_holder {
+			self.percentage_fee_bps as u128 * sender_near / 10_000u128
+		} else {
+			self.required_cost.0
+		}
+	}
+
+	// moves the offer to Settling and sends every escrowed NFT to its counterparty, `resolve_settlement` pays out once the transfers are in
+	pub(crate) fn internal_settle_offer(&mut self, hash: &Hash, offer: &HashOffer) {
+		let mut offer = offer.clone();
+		self.internal_set_status(&mut offer, OfferStatus::Settling);
+		self.hash_map.insert(hash, &offer);
+
+		let mut transfers: Option<Promise> = None;
+
+		for (nfts_data, new_owner_id) in offer.sent_nfts.iter().map(|x| (x, &offer.receiver_id))
+			.chain(offer.received_nfts.iter().map(|x| (x, &offer.sender_id))) {
+			let transfer = nft_transfer(nfts_data, new_owner_id);
+
+			transfers = Some(match transfers {
+				Some(joined) => joined.and(transfer),
+				None => transfer,
+			});
+		}
+
+		match transfers {
+			Some(joined) => {
+				joined.then(
+					Contract::ext(env::current_account_id()).with_static_gas(GAS_FOR_RESOLVE_SETTLEMENT)
+					.resolve_settlement(hash.clone())
+				);
+			},
+			// nothing to wait for
+			None => self.internal_finish_settlement(hash, &mut offer),
+		}
+	}
+
+	// pays out NEAR and the fee for an offer whose transfers all went through and closes it
+	pub(crate) fn internal_finish_settlement(&mut self, hash: &Hash, offer: &mut HashOffer) {
+		let mut nft_transfers = Vec::new();
+		let mut near_transfers = Vec::new();
+
+		for (nfts_data, old_owner_id, new_owner_id) in offer.sent_nfts.iter().map(|x| (x, &offer.sender_id, &offer.receiver_id))
+			.chain(offer.received_nfts.iter().map(|x| (x, &offer.receiver_id, &offer.sender_id))) {
+			self.internal_release_token(nfts_data);
+
+			nft_transfers.push(NftTransferLog {
+				contract_id: nfts_data.contract_id.clone(),
+				token_id: nfts_data.token_id.clone(),
+				old_owner_id: old_owner_id.clone(),
+				new_owner_id: new_owner_id.clone(),
+			});
+		}
+
+		let fee = offer.fee_paid;
+
+		Promise::new(self.fee_wallet.clone()).transfer(NearToken::from_yoctonear(fee));
+
+		if offer.sender_near > 0 {
+			// transfer near to the muhfucker
+			Promise::new(offer.receiver_id.clone()).transfer(NearToken::from_yoctonear(offer.sender_near));
+
+			near_transfers.push(NearTransferLog {
+				receiver_id: offer.receiver_id.clone(),
+				amount: U128(offer.sender_near),
+			});
+		}
+
+		emit_event(EventLogVariant::SwapSettled(vec![SwapSettledLog {
+			hash: hash.clone(),
+			nft_transfers,
+			near_transfers,
+			fee: NearTransferLog { receiver_id: self.fee_wallet.clone(), amount: U128(fee) },
+		}]));
+
+		self.internal_set_status(offer, OfferStatus::Settled);
+		self.internal_delete_offer(hash, offer);
+		self.internal_archive_offer(hash, offer, OfferOutcome::Settled, fee);
+		self.internal_record_trade(offer, fee);
+	}
+
+	// sends every escrowed NFT back to the party that deposited it
+	pub(crate) fn internal_return_escrow(&mut self, offer: &HashOffer) -> Vec<TokenData> {
+		for nfts_data in offer.sent_nfts.iter().chain(offer.received_nfts.iter()) {
+			self.internal_return_token(nfts_data);
+		}
+
+		offer.sent_nfts.iter().chain(offer.received_nfts.iter()).cloned().collect()
+	}
+
+	// NEAR side of a cancelled or expired offer: the base fee is kept, everything else goes back to the sender
+	pub(crate) fn internal_refund_closed_offer(&mut self, offer: &HashOffer) -> u128 {
+		let kept = offer.cancel_fee();
+		let refunded = offer.sender_near + offer.fee_paid - kept;
+
+		if kept > 0 {
+			Promise::new(self.fee_wallet.clone()).transfer(NearToken::from_yoctonear(kept));
+		}
+
+		if refunded > 0 {
+			Promise::new(offer.sender_id.clone()).transfer(NearToken::from_yoctonear(refunded));
+		}
+
+		refunded
+	}
+
+	// removes an offer from storage and from every index that references it
+	pub(crate) fn internal_delete_offer(&mut self, hash: &Hash, offer: &HashOffer) {
+		for account_id in [&offer.sender_id, &offer.receiver_id] {
+			if let Some(mut hash_arr) = self.hashes_per_owner.get(account_id) {
+				if let Some(index) = hash_arr.iter().position(|x| x == hash) {
+					hash_arr.remove(index);
+				}
+
+				// remove hash from storage
+				if hash_arr.is_empty() {
+					self.hashes_per_owner.remove(account_id);
+				} else {
+					self.hashes_per_owner.insert(account_id, &hash_arr);
+				}
+			}
+		}
+
+		self.open_offers.remove(hash);
+		self.hash_map.remove(hash);
+	}
+}
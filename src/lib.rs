@@ -1,30 +1,107 @@
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
-use near_sdk::collections::{LookupMap};
-use near_sdk::json_types::U128;
-use near_sdk::{near_bindgen, AccountId, require, env};
+use near_sdk::collections::{LookupMap, LookupSet, UnorderedMap, UnorderedSet};
+use near_sdk::json_types::{U128, U64};
+use near_sdk::{near_bindgen, AccountId, NearToken, PanicOnDefault, PublicKey, require, env};
 use::near_sdk::serde::{Serialize, Deserialize};
 
 
 pub use crate::account::*;
+pub use crate::archive::*;
+pub use crate::auction::*;
+pub use crate::bid::*;
+pub use crate::binding::*;
+pub use crate::config::*;
+pub use crate::curation::*;
+pub use crate::escrow::*;
+pub use crate::events::*;
+pub use crate::listing::*;
+pub use crate::pause::*;
+pub use crate::roles::*;
+pub use crate::signed::*;
+pub use crate::stats::*;
+pub use crate::status::*;
+pub use crate::timelock::*;
 
 mod account; 
+mod amend;
+mod archive;
+mod auction;
+mod bid;
+mod binding;
+mod blocklist;
+mod config;
+mod curation;
+mod enumeration;
+mod escrow;
+mod events;
+mod expiry;
+mod internal;
+mod listing;
+mod owner;
+mod pause;
+mod roles;
+mod signed;
+mod stats;
+mod status;
+mod timelock;
 
 pub type Hash = String;
 pub type TokenId = String;
 
 
 #[near_bindgen]
-#[derive(BorshDeserialize, BorshSerialize)]
+#[derive(BorshDeserialize, BorshSerialize, PanicOnDefault)]
+#[borsh(crate = "near_sdk::borsh")]
 pub struct Contract {
 	pub hash_map: LookupMap<Hash, HashOffer>,
 	pub hashes_per_owner: LookupMap<AccountId, Vec<Hash>>,
 	pub tokens_per_owner: LookupMap<AccountId, Vec<TokenData>>,
-	pub owner_id: String,
+	pub escrowed_tokens: LookupMap<EscrowKey, EscrowedToken>,
+	pub open_offers: UnorderedSet<Hash>,
+	pub status_counts: LookupMap<OfferStatus, u64>,
+	pub offer_expiry: u64,
+	pub archive: LookupMap<u64, ArchivedOffer>,
+	pub archive_by_hash: LookupMap<Hash, u64>,
+	pub archive_per_account: LookupMap<AccountId, ArchiveIndex>,
+	pub archive_per_collection: LookupMap<AccountId, ArchiveIndex>,
+	pub archive_first_id: u64,
+	pub archive_next_id: u64,
+	pub archive_retention: Option<u64>,
+	pub collection_stats: UnorderedMap<AccountId, TradeStats>,
+	pub account_stats: UnorderedMap<AccountId, TradeStats>,
+	pub total_stats: TradeStats,
+	pub owner_id: AccountId,
+	pub pending_owner: Option<AccountId>,
 	pub fee_wallet: AccountId,
 	pub required_cost: U128,
+	pub percentage_fee_threshold: U128,
+	pub percentage_fee_bps: u32,
+	pub holder_collection: AccountId,
+	pub pause: PauseState,
+	pub role_members: LookupMap<Role, Vec<AccountId>>,
+	pub collection_mode: CollectionMode,
+	pub allowed_collections: UnorderedSet<AccountId>,
+	pub denied_collections: UnorderedSet<AccountId>,
+	pub timelock_delay: u64,
+	pub pending_changes: UnorderedMap<u64, PendingChange>,
+	pub next_change_id: u64,
+	pub blocked_accounts: UnorderedSet<AccountId>,
+	pub offer_keys: LookupMap<AccountId, PublicKey>,
+	pub offer_balances: LookupMap<AccountId, u128>,
+	pub used_offer_nonces: LookupSet<(AccountId, u64)>,
+	pub hash_mode: HashMode,
+	pub listings: UnorderedMap<u64, Listing>,
+	pub next_listing_id: u64,
+	pub auctions: UnorderedMap<u64, Auction>,
+	pub next_auction_id: u64,
+	pub bids: LookupMap<u64, TokenBid>,
+	pub bids_per_token: LookupMap<EscrowKey, Vec<u64>>,
+	pub bids_per_account: LookupMap<AccountId, Vec<u64>>,
+	pub next_bid_id: u64,
 }
 
 #[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[borsh(crate = "near_sdk::borsh")]
 #[serde(crate = "near_sdk::serde")]
 pub struct HashOffer {
 	pub sender_id: AccountId,
@@ -36,41 +113,104 @@ pub struct HashOffer {
 	pub received_nfts: Vec<TokenData>,
 	pub timestamp: u64,
 	pub is_monarch: bool,
+	// fee taken with the deposit and the base fee in force at the time, later fee changes don't touch open offers
+	pub fee_paid: u128,
+	pub base_fee: u128,
+	// deadline fixed when the offer is created, 0 means it never expires
+	pub expires_at: u64,
+	pub status: OfferStatus,
+}
+
+impl HashOffer {
+	// both sides have deposited every NFT they owe
+	pub fn is_funded(&self) -> bool {
+		self.sent_nfts.len() == self.sender_nfts.len() && self.received_nfts.len() == self.receiver_nfts.len()
+	}
+
+	// part of the fee kept when the offer is cancelled or expires
+	pub fn cancel_fee(&self) -> u128 {
+		self.fee_paid.min(self.base_fee)
+	}
 }
 
 #[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[borsh(crate = "near_sdk::borsh")]
 #[serde(crate = "near_sdk::serde")]
 pub struct TokenData {
 	pub contract_id: AccountId,
 	pub token_id: TokenId,
 }
 
-impl Default for Contract {
-	fn default() -> Self {
+#[near_bindgen]
+impl Contract {
+	#[init]
+	pub fn new(
+		owner_id: AccountId,
+		fee_wallet: AccountId,
+		required_cost: U128,
+		holder_collection: AccountId,
+		archive_retention: Option<u64>,
+		timelock_delay: Option<U64>,
+	) -> Self {
 		Self {
 			hash_map: LookupMap::new(b"hash_map".to_vec()),
 			hashes_per_owner: LookupMap::new(b"hashes_per_owner".to_vec()),
 			tokens_per_owner: LookupMap::new(b"tokens_per_owner".to_vec()),
-			owner_id: "swap.genadrop.near".to_string(), // change me
-			fee_wallet: AccountId::new_unchecked("fee.genadrop.near".to_string()), // change me
-			required_cost: U128(100000000000000000000000),
+			escrowed_tokens: LookupMap::new(b"escrowed_tokens".to_vec()),
+			open_offers: UnorderedSet::new(b"open_offers".to_vec()),
+			status_counts: LookupMap::new(b"status_counts".to_vec()),
+			offer_expiry: 0,
+			archive: LookupMap::new(b"archive".to_vec()),
+			archive_by_hash: LookupMap::new(b"archive_by_hash".to_vec()),
+			archive_per_account: LookupMap::new(b"archive_per_account".to_vec()),
+			archive_per_collection: LookupMap::new(b"archive_per_collection".to_vec()),
+			archive_first_id: 0,
+			archive_next_id: 0,
+			archive_retention,
+			collection_stats: UnorderedMap::new(b"collection_stats".to_vec()),
+			account_stats: UnorderedMap::new(b"account_stats".to_vec()),
+			total_stats: TradeStats::default(),
+			owner_id,
+			pending_owner: None,
+			fee_wallet,
+			required_cost,
+			percentage_fee_threshold: U128(internal::DEFAULT_PERCENTAGE_FEE_THRESHOLD),
+			percentage_fee_bps: internal::DEFAULT_PERCENTAGE_FEE_BPS,
+			holder_collection,
+			pause: PauseState::default(),
+			role_members: LookupMap::new(b"role_members".to_vec()),
+			collection_mode: CollectionMode::Open,
+			allowed_collections: UnorderedSet::new(b"allowed_collections".to_vec()),
+			denied_collections: UnorderedSet::new(b"denied_collections".to_vec()),
+			timelock_delay: timelock_delay.map(u64::from).unwrap_or(DEFAULT_TIMELOCK_DELAY),
+			pending_changes: UnorderedMap::new(b"pending_changes".to_vec()),
+			next_change_id: 0,
+			blocked_accounts: UnorderedSet::new(b"blocked_accounts".to_vec()),
+			offer_keys: LookupMap::new(b"offer_keys".to_vec()),
+			offer_balances: LookupMap::new(b"offer_balances".to_vec()),
+			used_offer_nonces: LookupSet::new(b"used_offer_nonces".to_vec()),
+			hash_mode: HashMode::Free,
+			listings: UnorderedMap::new(b"listings".to_vec()),
+			next_listing_id: 0,
+			auctions: UnorderedMap::new(b"auctions".to_vec()),
+			next_auction_id: 0,
+			bids: LookupMap::new(b"bids".to_vec()),
+			bids_per_token: LookupMap::new(b"bids_per_token".to_vec()),
+			bids_per_account: LookupMap::new(b"bids_per_account".to_vec()),
+			next_bid_id: 0,
 		}
 	}
-}
 
-#[near_bindgen]
-impl Contract {
-    // ADD CONTRACT METHODS HERE
 	fn assert_owner(&self) {
-        require!(self.signer_is_owner(), "Method is private to owner")
+        require!(self.predecessor_is_owner(), "Method is private to owner")
     }
 
-    fn signer_is_owner(&self) -> bool {
-        self.is_owner(&env::signer_account_id())
+    fn predecessor_is_owner(&self) -> bool {
+        self.is_owner(&env::predecessor_account_id())
     }
 
     fn is_owner(&self, account: &AccountId) -> bool {
-        account.to_string() == self.owner_id
+        account == &self.owner_id
     }
 }
 
@@ -86,7 +226,7 @@ impl Contract {
 mod tests {
     use super::*;
     use near_sdk::test_utils::{get_logs, VMContextBuilder};
-    use near_sdk::{testing_env, AccountId};
+    use near_sdk::{testing_env, AccountId, PromiseOrValue};
 
     // part of writing unit tests is setting up a mock context
     // provide a `predecessor` here, it'll modify the default context
@@ -96,5 +236,819 @@ mod tests {
         builder
     }
 
-    // TESTS HERE
-}
\ No newline at end of file
+    // deposits attached to the nft_transfer calls issued so far, NEP-171 requires exactly 1 yocto on each
+    fn nft_transfer_deposits() -> Vec<NearToken> {
+        near_sdk::test_utils::get_created_receipts()
+            .into_iter()
+            .flat_map(|receipt| receipt.actions)
+            .filter_map(|action| match action {
+                near_sdk::mock::MockAction::FunctionCallWeight { method_name, attached_deposit, .. } if method_name == b"nft_transfer" => Some(attached_deposit),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn setup_contract(archive_retention: Option<u64>) -> Contract {
+        Contract::new(
+            "swap.genadrop.near".parse().unwrap(),
+            "fee.genadrop.near".parse().unwrap(),
+            U128(100000000000000000000000),
+            "mint.havendao.near".parse().unwrap(),
+            archive_retention,
+            None,
+        )
+    }
+
+    fn offer(sender: &str, receiver: &str) -> HashOffer {
+        HashOffer {
+            sender_id: sender.parse().unwrap(),
+            sender_near: 0,
+            sender_nfts: vec![],
+            sent_nfts: vec![],
+            receiver_id: receiver.parse().unwrap(),
+            receiver_nfts: vec![],
+            received_nfts: vec![],
+            timestamp: 0,
+            is_monarch: false,
+            fee_paid: 100000000000000000000000,
+            base_fee: 100000000000000000000000,
+            expires_at: 0,
+            status: OfferStatus::Created,
+        }
+    }
+
+    #[test]
+    fn add_and_delete_offer_updates_index() {
+        testing_env!(get_context("alice.near".parse().unwrap()).build());
+        let mut contract = setup_contract(None);
+
+        let first = offer("alice.near", "bob.near");
+        let second = offer("carol.near", "alice.near");
+        contract.internal_add_offer(&"a".to_string(), &first);
+        contract.internal_add_offer(&"b".to_string(), &second);
+        let logs = get_logs();
+        assert_eq!(logs.len(), 2);
+        assert!(logs[1].starts_with("EVENT_JSON:") && logs[1].contains("\"event\":\"offer_created\""));
+
+        assert_eq!(contract.total_open_offers().0, 2);
+        let page = contract.get_offers(Some(U128(1)), Some(10));
+        assert_eq!(page.len(), 1);
+        assert_eq!(page[0].0, "b");
+
+        contract.internal_delete_offer(&"a".to_string(), &first);
+        assert_eq!(contract.total_open_offers().0, 1);
+        assert_eq!(contract.get_hashes_for_owner("alice.near".parse().unwrap()), vec!["b".to_string()]);
+        assert!(contract.get_hashes_for_owner("bob.near".parse().unwrap()).is_empty());
+    }
+
+    #[test]
+    fn archive_keeps_history_within_retention() {
+        testing_env!(get_context("alice.near".parse().unwrap()).build());
+        let mut contract = setup_contract(Some(2));
+
+        let mut settled = offer("alice.near", "bob.near");
+        settled.sender_nfts.push(TokenData { contract_id: "nft.near".parse().unwrap(), token_id: "1".to_string() });
+        contract.internal_archive_offer(&"a".to_string(), &settled, OfferOutcome::Settled, 5);
+        contract.internal_archive_offer(&"b".to_string(), &offer("alice.near", "carol.near"), OfferOutcome::Cancelled, 0);
+        contract.internal_archive_offer(&"c".to_string(), &offer("bob.near", "alice.near"), OfferOutcome::Settled, 0);
+
+        assert_eq!(contract.total_archived_offers(), 2);
+        assert!(contract.get_archived_offer(0).is_none());
+        assert!(contract.get_collection_history("nft.near".parse().unwrap(), None, None).is_empty());
+
+        let history = contract.get_account_history("alice.near".parse().unwrap(), None, None);
+        assert_eq!(history.iter().map(|x| x.hash.as_str()).collect::<Vec<_>>(), vec!["b", "c"]);
+        assert_eq!(history[0].outcome, OfferOutcome::Cancelled);
+
+        let page = contract.get_account_history("alice.near".parse().unwrap(), Some(U128(1)), Some(1));
+        assert_eq!(page.iter().map(|x| x.hash.as_str()).collect::<Vec<_>>(), vec!["c"]);
+        assert!(contract.get_account_history("bob.near".parse().unwrap(), Some(U128(1)), None).is_empty());
+    }
+
+    #[test]
+    fn escrow_is_keyed_by_contract_and_token() {
+        testing_env!(get_context("alice.near".parse().unwrap()).build());
+        let mut contract = setup_contract(None);
+        let alice: AccountId = "alice.near".parse().unwrap();
+
+        let first = TokenData { contract_id: "one.near".parse().unwrap(), token_id: "1".to_string() };
+        let second = TokenData { contract_id: "two.near".parse().unwrap(), token_id: "1".to_string() };
+        contract.internal_escrow_token(&alice, &first, EscrowHolder::Offer("a".to_string()));
+        contract.internal_escrow_token(&alice, &second, EscrowHolder::Offer("b".to_string()));
+
+        contract.internal_release_token(&second);
+
+        let remaining = contract.get_tokens_for_owner(alice);
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].contract_id, first.contract_id);
+        assert_eq!(contract.get_escrowed_token(first.contract_id, "1".to_string()).unwrap().holder, EscrowHolder::Offer("a".to_string()));
+        assert!(contract.get_escrowed_token(second.contract_id, "1".to_string()).is_none());
+    }
+
+    #[test]
+    fn settled_trades_update_stats() {
+        testing_env!(get_context("alice.near".parse().unwrap()).build());
+        let mut contract = setup_contract(None);
+
+        let mut trade = offer("alice.near", "bob.near");
+        trade.sender_near = 20;
+        trade.sender_nfts.push(TokenData { contract_id: "nft.near".parse().unwrap(), token_id: "1".to_string() });
+        trade.receiver_nfts.push(TokenData { contract_id: "nft.near".parse().unwrap(), token_id: "2".to_string() });
+        contract.internal_record_trade(&trade, 3);
+        contract.internal_record_trade(&offer("bob.near", "carol.near"), 1);
+
+        let collection = contract.get_collection_stats("nft.near".parse().unwrap());
+        assert_eq!((collection.swap_count, collection.near_volume.0, collection.fees.0), (1, 20, 3));
+
+        let bob = contract.get_account_stats("bob.near".parse().unwrap());
+        assert_eq!((bob.swap_count, bob.near_volume.0, bob.fees.0), (2, 20, 4));
+
+        assert_eq!(contract.get_total_stats().swap_count, 2);
+        assert_eq!(contract.get_accounts_stats(Some(U128(1)), Some(5)).len(), 2);
+    }
+
+    #[test]
+    fn ownership_transfer_requires_acceptance() {
+        let owner: AccountId = "swap.genadrop.near".parse().unwrap();
+        let dao: AccountId = "dao.sputnik-dao.near".parse().unwrap();
+        testing_env!(get_context(owner.clone()).attached_deposit(NearToken::from_yoctonear(1)).build());
+        let mut contract = setup_contract(None);
+
+        contract.propose_owner(dao.clone());
+        assert_eq!(contract.get_owner(), owner);
+
+        testing_env!(get_context(dao.clone()).attached_deposit(NearToken::from_yoctonear(1)).build());
+        contract.accept_ownership();
+        assert_eq!(contract.get_owner(), dao);
+        assert!(contract.get_pending_owner().is_none());
+
+        let id = contract.propose_change(ParameterChange::HolderCollection { holder_collection: "nft.near".parse().unwrap() });
+        assert_eq!(contract.get_pending_change(id).unwrap().proposed_by, dao);
+    }
+
+    #[test]
+    #[should_panic(expected = "Method requires the FeeManager role")]
+    fn setters_reject_accounts_without_role() {
+        testing_env!(get_context("alice.near".parse().unwrap()).attached_deposit(NearToken::from_yoctonear(1)).build());
+        let mut contract = setup_contract(None);
+        contract.propose_change(ParameterChange::FeeWallet { fee_wallet: "alice.near".parse().unwrap() });
+    }
+
+    #[test]
+    fn granted_roles_unlock_admin_methods() {
+        let alice: AccountId = "alice.near".parse().unwrap();
+        testing_env!(get_context("swap.genadrop.near".parse().unwrap()).attached_deposit(NearToken::from_yoctonear(1)).build());
+        let mut contract = setup_contract(None);
+        contract.grant_role(Role::FeeManager, alice.clone());
+        assert_eq!(contract.get_role_members(Role::FeeManager), vec![alice.clone()]);
+
+        testing_env!(get_context(alice.clone()).attached_deposit(NearToken::from_yoctonear(1)).build());
+        contract.propose_change(ParameterChange::FeeWallet { fee_wallet: "treasury.near".parse().unwrap() });
+        assert_eq!(contract.get_pending_changes(None, None).len(), 1);
+
+        testing_env!(get_context("swap.genadrop.near".parse().unwrap()).attached_deposit(NearToken::from_yoctonear(1)).build());
+        contract.revoke_role(Role::FeeManager, alice.clone());
+        assert!(!contract.has_role(Role::FeeManager, alice));
+    }
+
+    #[test]
+    fn paused_deposits_return_the_token() {
+        let alice: AccountId = "alice.near".parse().unwrap();
+        testing_env!(get_context("swap.genadrop.near".parse().unwrap()).attached_deposit(NearToken::from_yoctonear(1)).build());
+        let mut contract = setup_contract(None);
+        contract.set_pause(PauseState { offers: true, deposits: true, settlement: false });
+
+        testing_env!(get_context("nft.near".parse().unwrap()).build());
+        let result = contract.nft_on_transfer(alice.clone(), alice, "1".to_string(), "a".to_string());
+        assert!(matches!(result, PromiseOrValue::Value(true)));
+    }
+
+    #[test]
+    fn collection_modes_filter_contracts() {
+        testing_env!(get_context("swap.genadrop.near".parse().unwrap()).attached_deposit(NearToken::from_yoctonear(1)).build());
+        let mut contract = setup_contract(None);
+        let good: AccountId = "good.near".parse().unwrap();
+        let scam: AccountId = "scam.near".parse().unwrap();
+
+        contract.allow_collections(vec![good.clone()]);
+        contract.deny_collections(vec![scam.clone()]);
+        assert!(contract.is_collection_allowed(scam.clone()));
+
+        contract.set_collection_mode(CollectionMode::AllowlistOnly);
+        assert!(contract.is_collection_allowed(good.clone()));
+        assert!(!contract.is_collection_allowed("other.near".parse().unwrap()));
+
+        contract.set_collection_mode(CollectionMode::Denylist);
+        assert!(!contract.is_collection_allowed(scam));
+        assert!(contract.is_collection_allowed("other.near".parse().unwrap()));
+    }
+
+    #[test]
+    fn admin_cancel_returns_escrow_and_logs_reason() {
+        testing_env!(get_context("swap.genadrop.near".parse().unwrap()).attached_deposit(NearToken::from_yoctonear(1)).build());
+        let mut contract = setup_contract(None);
+        let token = TokenData { contract_id: "nft.near".parse().unwrap(), token_id: "1".to_string() };
+
+        let mut stuck = offer("alice.near", "bob.near");
+        stuck.sender_nfts.push(token.clone());
+        stuck.sent_nfts.push(token.clone());
+        contract.internal_add_offer(&"a".to_string(), &stuck);
+        contract.internal_escrow_token(&stuck.sender_id, &token, EscrowHolder::Offer("a".to_string()));
+
+        contract.admin_cancel_offer("a".to_string(), "broken collection".to_string());
+
+        assert!(contract.get_transaction_data("a".to_string()).is_none());
+        let event = get_logs().pop().unwrap();
+        assert!(event.starts_with("EVENT_JSON:{\"standard\":\"genadropswap\""));
+        assert!(event.contains("\"reason\":\"broken collection\""));
+
+        // the record outlives a failed return so it can be retried, and goes once the token is back
+        assert_eq!(contract.get_escrowed_token(token.contract_id.clone(), token.token_id.clone()).unwrap().holder, EscrowHolder::Returning);
+        testing_env!(
+            get_context("swap.genadrop.near".parse().unwrap()).build(),
+            near_sdk::test_vm_config(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![near_sdk::PromiseResult::Failed],
+        );
+        contract.resolve_token_return(token.clone());
+        assert!(contract.get_escrowed_token(token.contract_id.clone(), token.token_id.clone()).is_some());
+        contract.retry_token_return(token.contract_id.clone(), token.token_id.clone());
+
+        testing_env!(
+            get_context("swap.genadrop.near".parse().unwrap()).build(),
+            near_sdk::test_vm_config(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![near_sdk::PromiseResult::Successful(vec![])],
+        );
+        contract.resolve_token_return(token.clone());
+        assert!(contract.get_escrowed_token(token.contract_id, token.token_id).is_none());
+        assert!(contract.get_tokens_for_owner("alice.near".parse().unwrap()).is_empty());
+    }
+
+    #[test]
+    fn fee_changes_wait_for_the_timelock() {
+        testing_env!(get_context("swap.genadrop.near".parse().unwrap()).attached_deposit(NearToken::from_yoctonear(1)).build());
+        let mut contract = setup_contract(None);
+
+        let id = contract.propose_change(ParameterChange::FeeSchedule {
+            required_cost: U128(5),
+            percentage_fee_threshold: U128(1000),
+            percentage_fee_bps: 250,
+        });
+        let eta = contract.get_pending_change(id).unwrap().eta;
+        assert_eq!(eta, DEFAULT_TIMELOCK_DELAY);
+
+        testing_env!(get_context("swap.genadrop.near".parse().unwrap()).attached_deposit(NearToken::from_yoctonear(1)).block_timestamp(eta).build());
+        contract.execute_change(id);
+
+        assert_eq!(contract.internal_fee_for(999, false), 5);
+        assert_eq!(contract.internal_fee_for(1000, false), 25);
+        assert_eq!(contract.internal_fee_for(1000, true), 5);
+        assert!(contract.get_pending_change(id).is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "is timelocked until")]
+    fn early_execution_is_rejected() {
+        testing_env!(get_context("swap.genadrop.near".parse().unwrap()).attached_deposit(NearToken::from_yoctonear(1)).build());
+        let mut contract = setup_contract(None);
+
+        let id = contract.propose_change(ParameterChange::FeeWallet { fee_wallet: "treasury.near".parse().unwrap() });
+        contract.execute_change(id);
+    }
+
+    #[test]
+    fn blocked_accounts_get_their_deposit_back() {
+        let mallory: AccountId = "mallory.near".parse().unwrap();
+        testing_env!(get_context("swap.genadrop.near".parse().unwrap()).attached_deposit(NearToken::from_yoctonear(1)).build());
+        let mut contract = setup_contract(None);
+        contract.block_accounts(vec![mallory.clone()]);
+        assert_eq!(contract.get_blocked_accounts(None, None), vec![mallory.clone()]);
+
+        testing_env!(get_context("nft.near".parse().unwrap()).build());
+        let result = contract.nft_on_transfer(mallory.clone(), mallory, "1".to_string(), "a".to_string());
+        assert!(matches!(result, PromiseOrValue::Value(true)));
+    }
+
+    #[test]
+    fn dao_deposits_are_credited_to_previous_owner() {
+        let dao: AccountId = "dao.sputnik-dao.near".parse().unwrap();
+        testing_env!(get_context("swap.genadrop.near".parse().unwrap()).build());
+        let mut contract = setup_contract(None);
+        let token = TokenData { contract_id: "nft.near".parse().unwrap(), token_id: "1".to_string() };
+
+        let mut proposal = offer("dao.sputnik-dao.near", "bob.near");
+        proposal.sender_nfts.push(token.clone());
+        proposal.receiver_nfts.push(TokenData { contract_id: "nft.near".parse().unwrap(), token_id: "2".to_string() });
+        contract.internal_add_offer(&"a".to_string(), &proposal);
+
+        // a council member signs the proposal execution, the DAO is the token owner
+        testing_env!(get_context("nft.near".parse().unwrap()).signer_account_id("council.near".parse().unwrap()).build());
+        contract.nft_on_transfer(dao.clone(), dao.clone(), "1".to_string(), "a".to_string());
+
+        let escrowed = contract.get_escrowed_token(token.contract_id, token.token_id).unwrap();
+        assert_eq!(escrowed.depositor_id, dao);
+        assert_eq!(contract.get_transaction_data("a".to_string()).unwrap().sent_nfts.len(), 1);
+    }
+
+    #[test]
+    fn config_reports_deployment() {
+        testing_env!(get_context("alice.near".parse().unwrap()).build());
+        let contract = setup_contract(Some(100));
+
+        let config = contract.get_config();
+        assert_eq!(config.version, env!("CARGO_PKG_VERSION"));
+        assert_eq!(config.owner_id.as_str(), "swap.genadrop.near");
+        assert_eq!(config.holder_collection.as_str(), "mint.havendao.near");
+        assert_eq!(config.max_nfts_per_offer, 8);
+        assert_eq!(config.archive_retention, Some(100));
+        assert!(!config.pause.offers);
+    }
+
+    #[test]
+    fn stale_offers_can_be_expired_by_anyone() {
+        testing_env!(get_context("swap.genadrop.near".parse().unwrap()).attached_deposit(NearToken::from_yoctonear(1)).build());
+        let mut contract = setup_contract(None);
+        contract.internal_add_offer(&"old".to_string(), &offer("alice.near", "bob.near"));
+        contract.set_offer_expiry(U64(100));
+
+        let mut trade = offer("alice.near", "bob.near");
+        trade.expires_at = contract.internal_expiry_deadline();
+        contract.internal_add_offer(&"a".to_string(), &trade);
+        assert!(!contract.is_offer_expired("a".to_string()));
+
+        // past the deadline deposits bounce even before anyone expires the offer
+        testing_env!(get_context("nft.near".parse().unwrap()).block_timestamp(100).build());
+        let alice: AccountId = "alice.near".parse().unwrap();
+        assert!(matches!(contract.nft_on_transfer(alice.clone(), alice, "1".to_string(), "a".to_string()), PromiseOrValue::Value(true)));
+
+        testing_env!(get_context("carol.near".parse().unwrap()).block_timestamp(100).build());
+        // offers created before expiry was switched on stay open
+        assert!(!contract.is_offer_expired("old".to_string()));
+        contract.expire_offer("a".to_string());
+
+        assert!(contract.get_transaction_data("a".to_string()).is_none());
+        assert_eq!(contract.get_offer_counts().expired, 1);
+        assert_eq!(contract.get_account_history("alice.near".parse().unwrap(), None, None)[0].outcome, OfferOutcome::Expired);
+        assert!(get_logs().pop().unwrap().contains("\"event\":\"offer_expired\""));
+    }
+
+    #[test]
+    fn failed_settlement_keeps_unmoved_tokens_in_escrow() {
+        let alice: AccountId = "alice.near".parse().unwrap();
+        let bob: AccountId = "bob.near".parse().unwrap();
+        testing_env!(get_context("swap.genadrop.near".parse().unwrap()).build());
+        let mut contract = setup_contract(None);
+
+        let mut trade = offer("alice.near", "bob.near");
+        trade.sender_nfts.push(TokenData { contract_id: "nft.near".parse().unwrap(), token_id: "1".to_string() });
+        trade.receiver_nfts.push(TokenData { contract_id: "nft.near".parse().unwrap(), token_id: "2".to_string() });
+        contract.internal_add_offer(&"a".to_string(), &trade);
+
+        testing_env!(get_context("nft.near".parse().unwrap()).build());
+        contract.nft_on_transfer(alice.clone(), alice, "1".to_string(), "a".to_string());
+        assert_eq!(contract.get_offer_status("a".to_string()), Some(OfferStatus::PartiallyFunded));
+        contract.nft_on_transfer(bob.clone(), bob, "2".to_string(), "a".to_string());
+        assert_eq!(contract.get_offer_status("a".to_string()), Some(OfferStatus::Settling));
+        assert_eq!(nft_transfer_deposits(), vec![NearToken::from_yoctonear(1); 2]);
+
+        testing_env!(
+            get_context("swap.genadrop.near".parse().unwrap()).build(),
+            near_sdk::test_vm_config(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![near_sdk::PromiseResult::Successful(vec![]), near_sdk::PromiseResult::Failed],
+        );
+        assert!(!contract.resolve_settlement("a".to_string()));
+
+        let stuck = contract.get_transaction_data("a".to_string()).unwrap();
+        assert_eq!(stuck.status, OfferStatus::Failed);
+        assert!(stuck.sent_nfts.is_empty());
+        assert_eq!(stuck.received_nfts[0].token_id, "2");
+        assert!(contract.get_escrowed_token("nft.near".parse().unwrap(), "1".to_string()).is_none());
+        assert_eq!(contract.get_offer_counts().failed, 1);
+
+        // only the token that didn't move is sent again
+        testing_env!(get_context("bob.near".parse().unwrap()).build());
+        contract.retry_settlement("a".to_string());
+        assert_eq!(contract.get_offer_status("a".to_string()), Some(OfferStatus::Settling));
+
+        testing_env!(
+            get_context("swap.genadrop.near".parse().unwrap()).build(),
+            near_sdk::test_vm_config(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![near_sdk::PromiseResult::Successful(vec![])],
+        );
+        assert!(contract.resolve_settlement("a".to_string()));
+        assert!(contract.get_transaction_data("a".to_string()).is_none());
+        assert!(contract.get_escrowed_token("nft.near".parse().unwrap(), "2".to_string()).is_none());
+    }
+
+    #[test]
+    fn progress_lists_missing_tokens_per_side() {
+        let alice: AccountId = "alice.near".parse().unwrap();
+        testing_env!(get_context("swap.genadrop.near".parse().unwrap()).build());
+        let mut contract = setup_contract(None);
+
+        let mut trade = offer("alice.near", "bob.near");
+        trade.sender_near = 7;
+        trade.sender_nfts.push(TokenData { contract_id: "nft.near".parse().unwrap(), token_id: "1".to_string() });
+        trade.receiver_nfts.push(TokenData { contract_id: "nft.near".parse().unwrap(), token_id: "2".to_string() });
+        contract.internal_add_offer(&"a".to_string(), &trade);
+
+        testing_env!(get_context("nft.near".parse().unwrap()).build());
+        contract.nft_on_transfer(alice.clone(), alice, "1".to_string(), "a".to_string());
+
+        let progress = contract.get_offer_progress("a".to_string()).unwrap();
+        assert_eq!(progress.sender.deposited_nfts.len(), 1);
+        assert!(progress.sender.outstanding_nfts.is_empty());
+        assert_eq!(progress.receiver.outstanding_nfts[0].token_id, "2");
+        assert_eq!(progress.near_escrowed.0, 7);
+        assert_eq!(progress.waiting_on, vec!["bob.near".parse::<AccountId>().unwrap()]);
+    }
+
+    #[test]
+    fn signed_offers_are_accepted_once() {
+        use ed25519_dalek::Signer;
+        use near_sdk::json_types::Base64VecU8;
+
+        let alice: AccountId = "alice.near".parse().unwrap();
+        let secret = ed25519_dalek::SecretKey::from_bytes(&[7; 32]).unwrap();
+        let keypair = ed25519_dalek::Keypair { public: (&secret).into(), secret };
+        let mut key = vec![0];
+        key.extend_from_slice(keypair.public.as_bytes());
+
+        // storage is paid out of the deposits, the first balance deposit keeps back the cost of its entry
+        testing_env!(get_context(alice.clone()).attached_deposit(NearToken::from_millinear(10)).build());
+        let mut contract = setup_contract(None);
+        contract.register_offer_key(PublicKey::try_from(key).unwrap());
+        let stored = contract.deposit_offer_balance();
+        assert!(stored.0 > 0 && stored.0 < NearToken::from_millinear(10).as_yoctonear());
+        testing_env!(get_context(alice.clone()).attached_deposit(NearToken::from_yoctonear(100000000000000000000050 - stored.0)).build());
+        assert_eq!(contract.deposit_offer_balance().0, 100000000000000000000050);
+
+        let payload = SignedOfferPayload {
+            contract_id: "alice.near".parse().unwrap(),
+            hash: "a".to_string(),
+            sender_id: alice.clone(),
+            sender_near: U128(50),
+            sender_nfts: vec![],
+            receiver_id: "bob.near".parse().unwrap(),
+            receiver_nfts: vec![],
+            nonce: U64(1),
+            expires_at: U64(100),
+        };
+        let signature = keypair.sign(&borsh::to_vec(&payload).unwrap()).to_bytes().to_vec();
+
+        testing_env!(get_context("bob.near".parse().unwrap()).current_account_id(alice.clone()).build());
+        contract.accept_signed_offer(payload.clone(), Base64VecU8(signature));
+
+        assert_eq!(contract.get_transaction_data("a".to_string()).unwrap().sender_near, 50);
+        assert_eq!(contract.get_offer_balance(alice.clone()).0, 0);
+        assert!(contract.is_offer_nonce_used(alice.clone(), U64(1)));
+
+        // a listed token the sender no longer owns credits the cost back to their balance
+        testing_env!(
+            get_context(alice.clone()).current_account_id(alice.clone()).build(),
+            near_sdk::test_vm_config(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![near_sdk::PromiseResult::Successful(b"{\"token_id\":\"1\",\"owner_id\":\"carol.near\"}".to_vec())],
+        );
+        let moved = SignedOfferPayload {
+            hash: "b".to_string(),
+            sender_nfts: vec![TokenData { contract_id: "nft.near".parse().unwrap(), token_id: "1".to_string() }],
+            nonce: U64(2),
+            ..payload
+        };
+        assert!(!contract.callback_accept_signed_offer(moved, U128(100000000000000000000000)));
+        assert!(contract.get_transaction_data("b".to_string()).is_none());
+        assert_eq!(contract.get_offer_balance(alice).0, 100000000000000000000050);
+    }
+
+    #[test]
+    #[should_panic(expected = "to cover storage")]
+    fn burning_a_nonce_pays_for_its_storage() {
+        testing_env!(get_context("alice.near".parse().unwrap()).attached_deposit(NearToken::from_yoctonear(1)).build());
+        let mut contract = setup_contract(None);
+        contract.cancel_offer_nonce(U64(1));
+    }
+
+    #[test]
+    fn bound_hashes_commit_to_the_offer_terms() {
+        testing_env!(get_context("swap.genadrop.near".parse().unwrap()).attached_deposit(NearToken::from_yoctonear(1)).build());
+        let mut contract = setup_contract(None);
+        contract.set_hash_mode(HashMode::Bound);
+
+        let trade = offer("alice.near", "bob.near");
+        let hash = contract.compute_offer_hash(OfferTerms::from(&trade));
+        assert_eq!(hash.len(), 64);
+        contract.assert_hash_binding(&hash, &OfferTerms::from(&trade));
+
+        contract.internal_add_offer(&hash, &trade);
+        contract.internal_add_offer(&"a".to_string(), &trade);
+        assert_eq!(contract.verify_offer_hash(hash), Some(true));
+        assert_eq!(contract.verify_offer_hash("a".to_string()), Some(false));
+        assert_eq!(contract.verify_offer_hash("b".to_string()), None);
+    }
+
+    #[test]
+    fn listings_sell_for_the_asking_price() {
+        let alice: AccountId = "alice.near".parse().unwrap();
+        let bob: AccountId = "bob.near".parse().unwrap();
+        let token = TokenData { contract_id: "nft.near".parse().unwrap(), token_id: "1".to_string() };
+        let price = U128(200000000000000000000000);
+        testing_env!(get_context("nft.near".parse().unwrap()).build());
+        let mut contract = setup_contract(None);
+
+        let msg = format!("{{\"type\":\"listing\",\"price\":\"{}\"}}", price.0);
+        let result = contract.nft_on_transfer(alice.clone(), alice.clone(), "1".to_string(), msg);
+        assert!(matches!(result, PromiseOrValue::Promise(_)));
+        assert_eq!(contract.get_escrowed_token(token.contract_id.clone(), "1".to_string()).unwrap().holder, EscrowHolder::Listing(0));
+
+        testing_env!(
+            get_context("swap.genadrop.near".parse().unwrap()).build(),
+            near_sdk::test_vm_config(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![near_sdk::PromiseResult::Successful(b"[]".to_vec())],
+        );
+        contract.callback_create_listing(0, alice.clone(), token.clone(), ListingPrice::Fixed { price });
+        assert!(!contract.get_listing(0).unwrap().is_holder);
+
+        testing_env!(get_context(bob.clone()).attached_deposit(NearToken::from_yoctonear(price.0)).build());
+        contract.buy(0);
+        assert!(contract.get_listing(0).is_none());
+        assert_eq!(nft_transfer_deposits(), vec![NearToken::from_yoctonear(1)]);
+
+        testing_env!(
+            get_context("swap.genadrop.near".parse().unwrap()).build(),
+            near_sdk::test_vm_config(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![near_sdk::PromiseResult::Successful(vec![])],
+        );
+        let listing = Listing { id: 0, seller_id: alice, token: token.clone(), pricing: ListingPrice::Fixed { price }, is_holder: false, created_at: 0 };
+        assert!(contract.resolve_purchase(listing, bob.clone(), price, contract.required_cost, price));
+
+        assert!(contract.get_escrowed_token(token.contract_id, token.token_id).is_none());
+        assert_eq!(contract.get_account_stats(bob).near_volume, price);
+        assert!(get_logs().pop().unwrap().contains("\"event\":\"listing_sold\""));
+    }
+
+    #[test]
+    fn late_bids_extend_the_auction() {
+        let token = TokenData { contract_id: "nft.near".parse().unwrap(), token_id: "1".to_string() };
+        let hour = 60 * 60 * 1_000_000_000;
+        testing_env!(
+            get_context("swap.genadrop.near".parse().unwrap()).build(),
+            near_sdk::test_vm_config(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![near_sdk::PromiseResult::Successful(b"[]".to_vec())],
+        );
+        let mut contract = setup_contract(None);
+        contract.internal_escrow_token(&"alice.near".parse().unwrap(), &token, EscrowHolder::Auction(0));
+        contract.callback_create_auction(0, "alice.near".parse().unwrap(), token.clone(), U128(1000), U128(100), U64(hour));
+
+        testing_env!(get_context("bob.near".parse().unwrap()).attached_deposit(NearToken::from_yoctonear(1000)).block_timestamp(1).build());
+        contract.place_bid(0);
+        assert_eq!(contract.get_auction(0).unwrap().ends_at, hour);
+
+        testing_env!(get_context("carol.near".parse().unwrap()).attached_deposit(NearToken::from_yoctonear(1100)).block_timestamp(hour - 1).build());
+        contract.place_bid(0);
+
+        let auction = contract.get_auction(0).unwrap();
+        assert_eq!(auction.highest_bid.as_ref().unwrap().bidder_id.as_str(), "carol.near");
+        assert_eq!(auction.ends_at, hour - 1 + ANTI_SNIPING_WINDOW);
+        assert_eq!(auction.min_bid(), 1200);
+
+        // the winning bid is below the base fee, so the winner is refunded instead of the token moving
+        testing_env!(get_context("carol.near".parse().unwrap()).block_timestamp(auction.ends_at).build());
+        assert!(matches!(contract.settle_auction(0), PromiseOrValue::Value(false)));
+        assert!(contract.get_auction(0).is_none());
+        assert_eq!(contract.get_escrowed_token(token.contract_id, token.token_id).unwrap().holder, EscrowHolder::Returning);
+        assert_eq!(nft_transfer_deposits(), vec![NearToken::from_yoctonear(1)]);
+        let event = get_logs().pop().unwrap();
+        assert!(event.contains("\"event\":\"auction_settled\"") && !event.contains("winner_id"));
+    }
+
+    #[test]
+    #[should_panic(expected = "Auction can run for at most")]
+    fn auctions_have_a_maximum_duration() {
+        let alice: AccountId = "alice.near".parse().unwrap();
+        testing_env!(get_context("nft.near".parse().unwrap()).build());
+        let mut contract = setup_contract(None);
+        let msg = format!("{{\"type\":\"auction\",\"reserve_price\":\"100000000000000000000000\",\"min_increment\":\"1\",\"duration\":\"{}\"}}", u64::MAX);
+        contract.nft_on_transfer(alice.clone(), alice, "1".to_string(), msg);
+    }
+
+    #[test]
+    fn dutch_listings_decay_to_the_floor() {
+        let linear = ListingPrice::Dutch { start_price: U128(1000), floor_price: U128(400), duration: U64(100), decay: PriceDecay::Linear };
+        let stepwise = ListingPrice::Dutch { start_price: U128(1000), floor_price: U128(400), duration: U64(100), decay: PriceDecay::Stepwise { steps: 3 } };
+        assert_eq!(linear.price_after(0), 1000);
+        assert_eq!(linear.price_after(25), 850);
+        assert_eq!(linear.price_after(500), 400);
+        assert_eq!(stepwise.price_after(32), 1000);
+        assert_eq!(stepwise.price_after(34), 800);
+        assert_eq!(stepwise.price_after(100), 400);
+
+        testing_env!(
+            get_context("swap.genadrop.near".parse().unwrap()).block_timestamp(10).build(),
+            near_sdk::test_vm_config(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![near_sdk::PromiseResult::Successful(b"[]".to_vec())],
+        );
+        let mut contract = setup_contract(None);
+        let token = TokenData { contract_id: "nft.near".parse().unwrap(), token_id: "1".to_string() };
+        contract.callback_create_listing(0, "alice.near".parse().unwrap(), token, linear);
+
+        testing_env!(get_context("bob.near".parse().unwrap()).block_timestamp(60).build());
+        assert_eq!(contract.get_listing_price(0), Some(U128(700)));
+    }
+
+    #[test]
+    fn accepted_bids_leave_the_indexes() {
+        let bob: AccountId = "bob.near".parse().unwrap();
+        testing_env!(get_context("alice.near".parse().unwrap()).attached_deposit(NearToken::from_yoctonear(100000000000000000000000)).build());
+        let mut contract = setup_contract(None);
+        let first = contract.create_bid("nft.near".parse().unwrap(), "1".to_string());
+        let second = contract.create_bid("nft.near".parse().unwrap(), "2".to_string());
+        assert_eq!(contract.get_bids_for_account("alice.near".parse().unwrap()).len(), 2);
+
+        testing_env!(get_context("nft.near".parse().unwrap()).build());
+        let msg = format!("{{\"type\":\"bid\",\"bid_id\":{}}}", first);
+        contract.pause.settlement = true;
+        let result = contract.nft_on_transfer(bob.clone(), bob.clone(), "1".to_string(), msg.clone());
+        assert!(matches!(result, PromiseOrValue::Value(true)));
+        assert!(contract.get_bid(first).is_some());
+
+        contract.pause.settlement = false;
+        let result = contract.nft_on_transfer(bob.clone(), bob, "1".to_string(), msg);
+        assert!(matches!(result, PromiseOrValue::Promise(_)));
+        // the promise is only scheduled once it is dropped
+        drop(result);
+        assert_eq!(nft_transfer_deposits(), vec![NearToken::from_yoctonear(1)]);
+
+        assert!(contract.get_bid(first).is_none());
+        assert!(contract.get_bids_for_token("nft.near".parse().unwrap(), "1".to_string()).is_empty());
+        assert_eq!(contract.get_bids_for_account("alice.near".parse().unwrap())[0].id, second);
+    }
+
+    #[test]
+    #[should_panic(expected = "Bid no longer covers the")]
+    fn bids_below_a_raised_fee_cant_be_accepted() {
+        let bob: AccountId = "bob.near".parse().unwrap();
+        testing_env!(get_context("alice.near".parse().unwrap()).attached_deposit(NearToken::from_yoctonear(100000000000000000000000)).build());
+        let mut contract = setup_contract(None);
+        let bid_id = contract.create_bid("nft.near".parse().unwrap(), "1".to_string());
+        contract.required_cost = U128(200000000000000000000000);
+
+        testing_env!(get_context("nft.near".parse().unwrap()).build());
+        let msg = format!("{{\"type\":\"bid\",\"bid_id\":{}}}", bid_id);
+        contract.nft_on_transfer(bob.clone(), bob, "1".to_string(), msg);
+    }
+
+    #[test]
+    fn offers_for_moved_tokens_are_rejected() {
+        let token = |id: &str| TokenData { contract_id: "nft.near".parse().unwrap(), token_id: id.to_string() };
+        testing_env!(
+            get_context("swap.genadrop.near".parse().unwrap()).build(),
+            near_sdk::test_vm_config(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![
+                near_sdk::PromiseResult::Successful(b"[]".to_vec()),
+                near_sdk::PromiseResult::Successful(b"{\"token_id\":\"1\",\"owner_id\":\"alice.near\"}".to_vec()),
+                near_sdk::PromiseResult::Successful(b"{\"token_id\":\"2\",\"owner_id\":\"carol.near\"}".to_vec()),
+            ],
+        );
+        let mut contract = setup_contract(None);
+
+        let stored = contract.callback_send_offer(
+            "a".to_string(),
+            "alice.near".parse().unwrap(),
+            U128(0),
+            vec![token("1")],
+            "bob.near".parse().unwrap(),
+            vec![token("2")],
+            false,
+            U128(100000000000000000000000),
+        );
+
+        assert!(!stored);
+        assert!(contract.get_transaction_data("a".to_string()).is_none());
+
+        // a hash taken while the lookups were in flight and a wrong holder claim are refunded, not panicked on
+        contract.internal_add_offer(&"b".to_string(), &offer("carol.near", "dave.near"));
+        assert!(!contract.callback_send_offer("b".to_string(), "alice.near".parse().unwrap(), U128(0), vec![], "bob.near".parse().unwrap(), vec![], false, U128(100000000000000000000000)));
+        assert!(get_logs().pop().unwrap().contains("offer already exists"));
+        assert!(!contract.callback_send_offer("c".to_string(), "alice.near".parse().unwrap(), U128(0), vec![], "bob.near".parse().unwrap(), vec![], true, U128(100000000000000000000000)));
+        assert!(get_logs().pop().unwrap().contains("invalid holder status"));
+    }
+
+    #[test]
+    fn amending_returns_dropped_deposits() {
+        let alice: AccountId = "alice.near".parse().unwrap();
+        let kept = TokenData { contract_id: "nft.near".parse().unwrap(), token_id: "1".to_string() };
+        let dropped = TokenData { contract_id: "nft.near".parse().unwrap(), token_id: "2".to_string() };
+        testing_env!(get_context("swap.genadrop.near".parse().unwrap()).build());
+        let mut contract = setup_contract(None);
+
+        let mut trade = offer("alice.near", "bob.near");
+        trade.sender_near = 50;
+        trade.sender_nfts = vec![kept.clone(), dropped.clone()];
+        trade.receiver_nfts.push(TokenData { contract_id: "nft.near".parse().unwrap(), token_id: "3".to_string() });
+        contract.internal_add_offer(&"a".to_string(), &trade);
+
+        testing_env!(get_context("nft.near".parse().unwrap()).build());
+        contract.nft_on_transfer(alice.clone(), alice.clone(), "2".to_string(), "a".to_string());
+
+        testing_env!(get_context(alice).build());
+        contract.amend_offer("a".to_string(), vec![kept], U128(20));
+
+        let amended = contract.get_transaction_data("a".to_string()).unwrap();
+        assert_eq!(amended.sender_near, 20);
+        assert_eq!(amended.status, OfferStatus::Created);
+        assert_eq!(contract.get_escrowed_token(dropped.contract_id, dropped.token_id).unwrap().holder, EscrowHolder::Returning);
+        let event = get_logs().pop().unwrap();
+        assert!(event.contains("\"event\":\"offer_amended\"") && event.contains("\"refunded_near\":\"30\""));
+    }
+
+    #[test]
+    fn cancelling_refunds_against_the_fee_paid() {
+        testing_env!(get_context("swap.genadrop.near".parse().unwrap()).build());
+        let mut contract = setup_contract(None);
+
+        let mut trade = offer("alice.near", "bob.near");
+        trade.sender_near = 1000;
+        trade.fee_paid = 300;
+        trade.base_fee = 100;
+        contract.internal_add_offer(&"a".to_string(), &trade);
+
+        // a later fee change doesn't reprice the open offer
+        contract.required_cost = U128(5000);
+
+        testing_env!(get_context("alice.near".parse().unwrap()).attached_deposit(NearToken::from_yoctonear(1)).build());
+        contract.cancel_offer("a".to_string());
+
+        let event = get_logs().pop().unwrap();
+        assert!(event.contains("\"refunded_near\":\"1200\""));
+    }
+
+    #[test]
+    fn support_unwinds_offers_that_keep_failing() {
+        testing_env!(get_context("swap.genadrop.near".parse().unwrap()).attached_deposit(NearToken::from_yoctonear(1)).build());
+        let mut contract = setup_contract(None);
+        let token = TokenData { contract_id: "nft.near".parse().unwrap(), token_id: "2".to_string() };
+
+        let mut stuck = offer("alice.near", "bob.near");
+        stuck.sender_near = 50;
+        stuck.receiver_nfts.push(token.clone());
+        stuck.received_nfts.push(token.clone());
+        stuck.status = OfferStatus::Failed;
+        contract.internal_add_offer(&"a".to_string(), &stuck);
+        contract.internal_escrow_token(&stuck.receiver_id, &token, EscrowHolder::Offer("a".to_string()));
+
+        contract.admin_unwind_failed_offer("a".to_string(), "collection rejects transfers".to_string());
+
+        assert!(contract.get_transaction_data("a".to_string()).is_none());
+        assert_eq!(contract.get_escrowed_token(token.contract_id, token.token_id).unwrap().depositor_id.as_str(), "bob.near");
+        assert_eq!(nft_transfer_deposits(), vec![NearToken::from_yoctonear(1)]);
+        assert!(get_logs().pop().unwrap().contains("\"refunded_near\":\"100000000000000000000050\""));
+    }
+
+    #[test]
+    fn cancelled_listings_keep_the_token_until_it_is_back() {
+        let alice: AccountId = "alice.near".parse().unwrap();
+        let token = TokenData { contract_id: "nft.near".parse().unwrap(), token_id: "1".to_string() };
+        testing_env!(get_context("nft.near".parse().unwrap()).build());
+        let mut contract = setup_contract(None);
+
+        contract.pause.offers = true;
+        let msg = "{\"type\":\"listing\",\"price\":\"200000000000000000000000\"}".to_string();
+        assert!(matches!(contract.nft_on_transfer(alice.clone(), alice.clone(), "1".to_string(), msg.clone()), PromiseOrValue::Value(true)));
+        assert!(contract.get_escrowed_token(token.contract_id.clone(), token.token_id.clone()).is_none());
+
+        contract.pause.offers = false;
+        contract.nft_on_transfer(alice.clone(), alice.clone(), "1".to_string(), msg);
+        testing_env!(
+            get_context("swap.genadrop.near".parse().unwrap()).build(),
+            near_sdk::test_vm_config(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![near_sdk::PromiseResult::Successful(b"[]".to_vec())],
+        );
+        contract.callback_create_listing(0, alice.clone(), token.clone(), ListingPrice::Fixed { price: U128(200000000000000000000000) });
+
+        testing_env!(get_context(alice).attached_deposit(NearToken::from_yoctonear(1)).build());
+        contract.cancel_listing(0);
+        assert_eq!(contract.get_escrowed_token(token.contract_id, token.token_id).unwrap().holder, EscrowHolder::Returning);
+        assert_eq!(nft_transfer_deposits(), vec![NearToken::from_yoctonear(1)]);
+    }
+}
@@ -0,0 +1,306 @@
+use crate::*;
+use near_sdk::{assert_one_yocto, Gas, Promise, PromiseOrValue, PromiseResult};
+
+const GAS_FOR_LISTING_CALLBACK: Gas = Gas::from_tgas(10);
+const GAS_FOR_RESOLVE_PURCHASE: Gas = Gas::from_tgas(20);
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub enum PriceDecay {
+	Linear,
+	// drops in `steps` equal amounts, one at the end of each equal slice of the duration
+	Stepwise { steps: u32 },
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub enum ListingPrice {
+	Fixed {
+		price: U128,
+	},
+	// Dutch auction, falls from start_price to floor_price over duration and stays at the floor
+	Dutch {
+		start_price: U128,
+		floor_price: U128,
+		duration: U64,
+		decay: PriceDecay,
+	},
+}
+
+impl ListingPrice {
+	// price after `elapsed` nanoseconds on the market
+	pub fn price_after(&self, elapsed: u64) -> u128 {
+		match self {
+			ListingPrice::Fixed { price } => price.0,
+			ListingPrice::Dutch { start_price, floor_price, duration, decay } => {
+				let elapsed = elapsed.min(duration.0);
+				let drop = start_price.0 - floor_price.0;
+
+				let decayed = match decay {
+					PriceDecay::Linear => mul_div(drop, elapsed as u128, duration.0 as u128),
+					PriceDecay::Stepwise { steps } => {
+						let steps_taken = mul_div(*steps as u128, elapsed as u128, duration.0 as u128);
+						mul_div(drop, steps_taken, *steps as u128)
+					},
+				};
+
+				start_price.0 - decayed
+			},
+		}
+	}
+
+	// lowest price the listing can reach, the fee has to fit in it
+	pub fn min_price(&self) -> u128 {
+		match self {
+			ListingPrice::Fixed { price } => price.0,
+			ListingPrice::Dutch { floor_price, .. } => floor_price.0,
+		}
+	}
+}
+
+// an escrowed NFT for sale at a fixed or descending NEAR price
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub struct Listing {
+	pub id: u64,
+	pub seller_id: AccountId,
+	pub token: TokenData,
+	pub pricing: ListingPrice,
+	// holders pay the base fee whatever the price, like offer senders
+	pub is_holder: bool,
+	pub created_at: u64,
+}
+
+impl Listing {
+	pub fn current_price(&self) -> u128 {
+		self.pricing.price_after(env::block_timestamp().saturating_sub(self.created_at))
+	}
+}
+
+#[near_bindgen]
+impl Contract {
+
+	pub fn get_listing(&self, listing_id: u64) -> Option<Listing> {
+		self.listings.get(&listing_id)
+	}
+
+	// what `buy` charges right now
+	pub fn get_listing_price(&self, listing_id: u64) -> Option<U128> {
+		self.listings.get(&listing_id).map(|listing| U128(listing.current_price()))
+	}
+
+	pub fn get_listings(&self, from_index: Option<U128>, limit: Option<u64>) -> Vec<Listing> {
+		let start = u128::from(from_index.unwrap_or(U128(0)));
+
+		self.listings
+			.values()
+			.skip(start as usize)
+			.take(limit.unwrap_or(50) as usize)
+			.collect()
+	}
+
+	// stores the listing once the seller's holder status is in, the token stays escrowed either way
+	#[private]
+	pub fn callback_create_listing(
+		&mut self,
+		listing_id: u64,
+		seller_id: AccountId,
+		token: TokenData,
+		pricing: ListingPrice,
+	) -> bool {
+		let is_holder = promise_is_holder(0);
+
+		let listing = Listing {
+			id: listing_id,
+			seller_id,
+			token,
+			pricing,
+			is_holder,
+			created_at: env::block_timestamp(),
+		};
+
+		self.listings.insert(&listing_id, &listing);
+
+		emit_event(EventLogVariant::ListingCreated(vec![ListingCreatedLog {
+			listing_id,
+			seller_id: listing.seller_id,
+			contract_id: listing.token.contract_id,
+			token_id: listing.token.token_id,
+			pricing: listing.pricing,
+		}]));
+
+		false
+	}
+
+	// the first buyer attaching the current price wins, anything above it is sent back once the NFT has moved
+	#[payable]
+	pub fn buy(
+		&mut self,
+		listing_id: u64,
+	) -> Promise {
+		self.assert_settlement_not_paused();
+
+		let listing = self.listings.get(&listing_id).expect("Listing not found");
+		let buyer_id = env::predecessor_account_id();
+		let deposit = env::attached_deposit().as_yoctonear();
+
+		assert_ne!(
+			buyer_id,
+			listing.seller_id,
+			"Seller can't buy their own listing"
+		);
+
+		self.assert_not_blocked(&buyer_id);
+
+		let price = listing.current_price();
+
+		assert!(
+			deposit >= price,
+			"Must attach {} yoctoNEAR",
+			price
+		);
+
+		// priced before the token moves, a fee raised since listing can't leave the callback short
+		let fee = self.internal_fee_for(price, listing.is_holder);
+
+		assert!(
+			price >= fee,
+			"Listing price no longer covers the {} yoctoNEAR fee",
+			fee
+		);
+
+		// taken off the market while the transfer is in flight
+		self.listings.remove(&listing_id);
+
+		nft_transfer(&listing.token, &buyer_id)
+			.then(
+				Self::ext(env::current_account_id()).with_static_gas(GAS_FOR_RESOLVE_PURCHASE)
+				.resolve_purchase(listing, buyer_id, U128(price), U128(fee), U128(deposit))
+			)
+	}
+
+	// pays the seller once the NFT reached the buyer, otherwise refunds the buyer and relists
+	#[private]
+	pub fn resolve_purchase(
+		&mut self,
+		listing: Listing,
+		buyer_id: AccountId,
+		price: U128,
+		fee: U128,
+		deposit: U128,
+	) -> bool {
+		if !matches!(env::promise_result(0), PromiseResult::Successful(_)) {
+			Promise::new(buyer_id).transfer(NearToken::from_yoctonear(deposit.0));
+			self.listings.insert(&listing.id, &listing);
+
+			env::log_str(format!("Transfer for listing {} failed, buyer refunded", listing.id).as_str());
+			return false
+		}
+
+		self.internal_release_token(&listing.token);
+
+		let price = price.0;
+		let fee = fee.0;
+
+		Promise::new(self.fee_wallet.clone()).transfer(NearToken::from_yoctonear(fee));
+		Promise::new(listing.seller_id.clone()).transfer(NearToken::from_yoctonear(price - fee));
+
+		if deposit.0 > price {
+			Promise::new(buyer_id.clone()).transfer(NearToken::from_yoctonear(deposit.0 - price));
+		}
+
+		self.internal_record_sale(&listing.seller_id, &buyer_id, &listing.token, price, fee);
+
+		emit_event(EventLogVariant::ListingSold(vec![ListingSoldLog {
+			listing_id: listing.id,
+			seller_id: listing.seller_id,
+			buyer_id,
+			contract_id: listing.token.contract_id,
+			token_id: listing.token.token_id,
+			price: U128(price),
+			fee: NearTransferLog { receiver_id: self.fee_wallet.clone(), amount: U128(fee) },
+		}]));
+
+		true
+	}
+
+	#[payable]
+	pub fn cancel_listing(
+		&mut self,
+		listing_id: u64,
+	) {
+		assert_one_yocto();
+
+		let listing = self.listings.get(&listing_id).expect("Listing not found");
+
+		assert_eq!(
+			env::predecessor_account_id(),
+			listing.seller_id,
+			"Caller is not the seller"
+		);
+
+		self.listings.remove(&listing_id);
+		self.internal_return_token(&listing.token);
+
+		emit_event(EventLogVariant::ListingCancelled(vec![ListingCancelledLog {
+			listing_id,
+			seller_id: listing.seller_id,
+			contract_id: listing.token.contract_id,
+			token_id: listing.token.token_id,
+		}]));
+	}
+}
+
+impl Contract {
+	// escrows a token sent with a listing msg, the listing is stored once the seller's holder status is known
+	pub(crate) fn internal_deposit_listing(&mut self, seller_id: AccountId, token: TokenData, pricing: ListingPrice) -> PromiseOrValue<bool> {
+		// hand the token straight back while new offers are paused
+		if self.pause.offers {
+			env::log_str("offers are paused, returning token");
+			return PromiseOrValue::Value(true)
+		}
+
+		if let ListingPrice::Dutch { start_price, floor_price, duration, decay } = &pricing {
+			assert!(
+				start_price.0 > floor_price.0,
+				"Start price must be above the floor price"
+			);
+
+			assert!(
+				duration.0 > 0,
+				"Duration can't be 0"
+			);
+
+			assert!(
+				*decay != PriceDecay::Stepwise { steps: 0 },
+				"Stepwise decay needs at least one step"
+			);
+		}
+
+		assert!(
+			pricing.min_price() >= self.required_cost.0,
+			"Price must cover the {} yoctoNEAR fee",
+			self.required_cost.0
+		);
+
+		let listing_id = self.next_listing_id;
+		self.next_listing_id += 1;
+
+		self.internal_escrow_token(&seller_id, &token, EscrowHolder::Listing(listing_id));
+
+		let promise = ext_nft_contract::ext(self.holder_collection.clone()).nft_tokens_for_owner(seller_id.clone(), "0".to_string(), 1);
+
+		PromiseOrValue::Promise(promise.then(
+			Self::ext(env::current_account_id()).with_static_gas(GAS_FOR_LISTING_CALLBACK)
+			.callback_create_listing(listing_id, seller_id, token, pricing)
+		))
+	}
+}
+
+// a * b / c without overflowing when a * b doesn't fit in a u128, b must not exceed c
+fn mul_div(a: u128, b: u128, c: u128) -> u128 {
+	a / c * b + a % c * b / c
+}
@@ -0,0 +1,41 @@
+use crate::*;
+use near_sdk::assert_one_yocto;
+
+#[near_bindgen]
+impl Contract {
+
+	pub fn get_owner(&self) -> AccountId {
+		self.owner_id.clone()
+	}
+
+	pub fn get_pending_owner(&self) -> Option<AccountId> {
+		self.pending_owner.clone()
+	}
+
+	// first step of an ownership transfer, the new owner has to accept it
+	#[payable]
+	pub fn propose_owner(&mut self, new_owner_id: AccountId) {
+		assert_one_yocto();
+		self.assert_owner();
+
+		env::log_str(format!("Proposed owner: {}", &new_owner_id).as_str());
+		self.pending_owner = Some(new_owner_id);
+	}
+
+	#[payable]
+	pub fn accept_ownership(&mut self) {
+		assert_one_yocto();
+
+		let account = env::predecessor_account_id();
+
+		assert!(
+			self.pending_owner.as_ref() == Some(&account),
+			"Only the proposed owner can accept ownership"
+		);
+
+		self.owner_id = account;
+		self.pending_owner = None;
+
+		env::log_str(format!("Ownership accepted by: {}", &self.owner_id).as_str());
+	}
+}
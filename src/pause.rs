@@ -0,0 +1,39 @@
+use crate::*;
+use near_sdk::assert_one_yocto;
+
+// cancellation and refunds are never paused
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Default)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub struct PauseState {
+	pub offers: bool,
+	pub deposits: bool,
+	pub settlement: bool,
+}
+
+#[near_bindgen]
+impl Contract {
+
+	pub fn get_pause(&self) -> PauseState {
+		self.pause.clone()
+	}
+
+	#[payable]
+	pub fn set_pause(&mut self, pause: PauseState) {
+		assert_one_yocto();
+		self.assert_role(Role::Pauser);
+
+		env::log_str(format!("Pause state: offers={} deposits={} settlement={}", pause.offers, pause.deposits, pause.settlement).as_str());
+		self.pause = pause;
+	}
+}
+
+impl Contract {
+	pub(crate) fn assert_offers_not_paused(&self) {
+		assert!(!self.pause.offers, "New offers are paused");
+	}
+
+	pub(crate) fn assert_settlement_not_paused(&self) {
+		assert!(!self.pause.settlement, "Settlement is paused");
+	}
+}
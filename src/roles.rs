@@ -0,0 +1,70 @@
+use crate::*;
+use near_sdk::assert_one_yocto;
+
+// the owner implicitly holds every role
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub enum Role {
+	FeeManager,
+	Pauser,
+	Curator,
+	Support,
+	Moderator,
+}
+
+#[near_bindgen]
+impl Contract {
+
+	pub fn get_role_members(&self, role: Role) -> Vec<AccountId> {
+		self.role_members.get(&role).unwrap_or_default()
+	}
+
+	pub fn has_role(&self, role: Role, account_id: AccountId) -> bool {
+		self.get_role_members(role).contains(&account_id)
+	}
+
+	#[payable]
+	pub fn grant_role(&mut self, role: Role, account_id: AccountId) {
+		assert_one_yocto();
+		self.assert_owner();
+
+		let mut members = self.get_role_members(role);
+
+		if !members.contains(&account_id) {
+			members.push(account_id.clone());
+			self.role_members.insert(&role, &members);
+		}
+
+		env::log_str(format!("Granted {:?} to {}", role, &account_id).as_str());
+	}
+
+	#[payable]
+	pub fn revoke_role(&mut self, role: Role, account_id: AccountId) {
+		assert_one_yocto();
+		self.assert_owner();
+
+		let mut members = self.get_role_members(role);
+		members.retain(|x| x != &account_id);
+
+		if members.is_empty() {
+			self.role_members.remove(&role);
+		} else {
+			self.role_members.insert(&role, &members);
+		}
+
+		env::log_str(format!("Revoked {:?} from {}", role, &account_id).as_str());
+	}
+}
+
+impl Contract {
+	pub(crate) fn assert_role(&self, role: Role) {
+		let account = env::predecessor_account_id();
+
+		assert!(
+			self.is_owner(&account) || self.has_role(role, account),
+			"Method requires the {:?} role",
+			role
+		);
+	}
+}
@@ -0,0 +1,316 @@
+use crate::*;
+use near_sdk::json_types::Base64VecU8;
+use near_sdk::{assert_one_yocto, CurveType, Gas, Promise, PromiseOrValue, PublicKey};
+
+const GAS_FOR_SIGNED_OFFER_CALLBACK: Gas = Gas::from_tgas(20);
+
+// terms the sender signs off-chain, the receiver submits them together with the signature
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub struct SignedOfferPayload {
+	// this contract, so a signature can't be replayed against another deployment
+	pub contract_id: AccountId,
+	pub hash: Hash,
+	pub sender_id: AccountId,
+	pub sender_near: U128,
+	pub sender_nfts: Vec<TokenData>,
+	pub receiver_id: AccountId,
+	pub receiver_nfts: Vec<TokenData>,
+	pub nonce: U64,
+	pub expires_at: U64,
+}
+
+#[near_bindgen]
+impl Contract {
+
+	pub fn get_offer_key(&self, account_id: AccountId) -> Option<PublicKey> {
+		self.offer_keys.get(&account_id)
+	}
+
+	pub fn get_offer_balance(&self, account_id: AccountId) -> U128 {
+		U128(self.offer_balances.get(&account_id).unwrap_or(0))
+	}
+
+	pub fn is_offer_nonce_used(&self, account_id: AccountId, nonce: U64) -> bool {
+		self.used_offer_nonces.contains(&(account_id, nonce.0))
+	}
+
+	// replaces any key registered before, offers signed with the old key stop being accepted
+	#[payable]
+	pub fn register_offer_key(&mut self, public_key: PublicKey) {
+		assert!(
+			public_key.curve_type() == CurveType::ED25519,
+			"Only ed25519 keys can sign offers"
+		);
+
+		let initial_storage = env::storage_usage();
+		let account_id = env::predecessor_account_id();
+		self.offer_keys.insert(&account_id, &public_key);
+		refund_storage_deposit(initial_storage);
+
+		env::log_str(format!("Registered offer key for {}", account_id).as_str());
+	}
+
+	#[payable]
+	pub fn remove_offer_key(&mut self) {
+		assert_one_yocto();
+
+		let account_id = env::predecessor_account_id();
+		self.offer_keys.remove(&account_id);
+
+		env::log_str(format!("Removed offer key for {}", account_id).as_str());
+	}
+
+	// signed offers draw `sender_near` and the fee from this balance when they are accepted.
+	// the first deposit also pays for the balance's own storage
+	#[payable]
+	pub fn deposit_offer_balance(&mut self) -> U128 {
+		let attached_deposit = env::attached_deposit().as_yoctonear();
+
+		assert!(
+			attached_deposit > 0,
+			"Attach a deposit to add to the offer balance"
+		);
+
+		let initial_storage = env::storage_usage();
+		let account_id = env::predecessor_account_id();
+		let balance = self.offer_balances.get(&account_id).unwrap_or(0) + attached_deposit;
+		self.offer_balances.insert(&account_id, &balance);
+
+		let storage_cost = storage_cost_since(initial_storage);
+		assert!(
+			attached_deposit > storage_cost,
+			"Attach more than {} yoctoNEAR, the first deposit pays for its storage",
+			storage_cost
+		);
+
+		let balance = balance - storage_cost;
+		self.offer_balances.insert(&account_id, &balance);
+		U128(balance)
+	}
+
+	#[payable]
+	pub fn withdraw_offer_balance(&mut self, amount: Option<U128>) -> U128 {
+		assert_one_yocto();
+
+		let account_id = env::predecessor_account_id();
+		let balance = self.offer_balances.get(&account_id).unwrap_or(0);
+		let amount = amount.map(u128::from).unwrap_or(balance);
+
+		assert!(
+			amount <= balance,
+			"Insufficient offer balance"
+		);
+
+		self.internal_set_offer_balance(&account_id, balance - amount);
+		Promise::new(account_id).transfer(NearToken::from_yoctonear(amount));
+
+		U128(balance - amount)
+	}
+
+	// burns a nonce so an offer that was handed out can no longer be accepted
+	#[payable]
+	pub fn cancel_offer_nonce(&mut self, nonce: U64) {
+		let initial_storage = env::storage_usage();
+		let account_id = env::predecessor_account_id();
+		self.used_offer_nonces.insert(&(account_id.clone(), nonce.0));
+		refund_storage_deposit(initial_storage);
+
+		env::log_str(format!("Cancelled offer nonce {} for {}", nonce.0, account_id).as_str());
+	}
+
+	// the receiver materialises a signed offer, signed offers skip the holder check and pay the standard fee.
+	// the listed tokens go through the same nft_token ownership check as send_offer
+	pub fn accept_signed_offer(
+		&mut self,
+		payload: SignedOfferPayload,
+		signature: Base64VecU8,
+	) -> PromiseOrValue<bool> {
+		self.assert_offers_not_paused();
+
+		let account = env::predecessor_account_id();
+
+		assert_eq!(
+			account,
+			payload.receiver_id,
+			"Only the receiver can accept a signed offer"
+		);
+
+		assert_eq!(
+			payload.contract_id,
+			env::current_account_id(),
+			"Offer was signed for another contract"
+		);
+
+		assert!(
+			env::block_timestamp() < payload.expires_at.0,
+			"Signed offer has expired"
+		);
+
+		assert!(
+			self.used_offer_nonces.insert(&(payload.sender_id.clone(), payload.nonce.0)),
+			"Offer nonce already used"
+		);
+
+		let public_key = self.offer_keys.get(&payload.sender_id).expect("Sender has no offer key");
+		let message = borsh::to_vec(&payload).unwrap();
+
+		assert!(
+			verify_signature(&public_key, &message, &signature.0),
+			"Invalid offer signature"
+		);
+
+		assert!(
+			!self.hash_map.contains_key(&payload.hash),
+			"Offer already exists",
+		);
+
+		assert_ne!(
+			payload.sender_id,
+			payload.receiver_id,
+			"Can't be receiver"
+		);
+
+		self.assert_not_blocked(&payload.sender_id);
+		self.assert_not_blocked(&payload.receiver_id);
+
+		assert!(
+			(payload.sender_nfts.len() + payload.receiver_nfts.len()) <= MAX_NFTS_PER_OFFER,
+			"Maximum NFTS per transaction is {}",
+			MAX_NFTS_PER_OFFER
+		);
+
+		for nfts_data in payload.sender_nfts.iter().chain(payload.receiver_nfts.iter()) {
+			self.assert_collection_allowed(&nfts_data.contract_id);
+		}
+
+		self.assert_hash_binding(&payload.hash, &OfferTerms {
+			sender_id: payload.sender_id.clone(),
+			sender_near: payload.sender_near,
+			sender_nfts: payload.sender_nfts.clone(),
+			receiver_id: payload.receiver_id.clone(),
+			receiver_nfts: payload.receiver_nfts.clone(),
+		});
+
+		let fee = self.internal_fee_for(payload.sender_near.0, false);
+		let cost = payload.sender_near.0 + fee;
+		let balance = self.offer_balances.get(&payload.sender_id).unwrap_or(0);
+
+		assert!(
+			cost <= balance,
+			"Sender's offer balance can't cover {} yoctoNEAR",
+			cost
+		);
+
+		self.internal_set_offer_balance(&payload.sender_id, balance - cost);
+
+		match nft_token_lookups(&payload.sender_nfts, &payload.receiver_nfts).into_iter().reduce(Promise::and) {
+			Some(lookups) => PromiseOrValue::Promise(lookups.then(
+				Self::ext(env::current_account_id()).with_static_gas(GAS_FOR_SIGNED_OFFER_CALLBACK)
+				.callback_accept_signed_offer(payload, U128(fee))
+			)),
+			// nothing to look up
+			None => {
+				self.internal_add_signed_offer(payload, fee);
+				PromiseOrValue::Value(true)
+			},
+		}
+	}
+
+	// stores the signed offer once the ownership checks are in, a rejected offer credits its cost back
+	// to the sender's offer balance. the nonce stays used either way
+	#[private]
+	pub fn callback_accept_signed_offer(
+		&mut self,
+		payload: SignedOfferPayload,
+		fee: U128,
+	) -> bool {
+		let rejected = if self.hash_map.contains_key(&payload.hash) {
+			Some("offer already exists")
+		} else if !promise_owners_match(0, &payload.sender_id, &payload.sender_nfts, &payload.receiver_id, &payload.receiver_nfts) {
+			Some("listed tokens are not owned by the offer parties")
+		} else {
+			None
+		};
+
+		if let Some(reason) = rejected {
+			let balance = self.offer_balances.get(&payload.sender_id).unwrap_or(0);
+			self.internal_set_offer_balance(&payload.sender_id, balance + payload.sender_near.0 + fee.0);
+
+			env::log_str(format!("Signed offer {} rejected, {}", payload.hash, reason).as_str());
+			return false
+		}
+
+		self.internal_add_signed_offer(payload, fee.0);
+		true
+	}
+}
+
+impl Contract {
+	fn internal_add_signed_offer(&mut self, payload: SignedOfferPayload, fee: u128) {
+		let transaction_data = HashOffer {
+			sender_id: payload.sender_id,
+			sender_near: payload.sender_near.0,
+			sender_nfts: payload.sender_nfts,
+			sent_nfts: Vec::new(),
+			receiver_id: payload.receiver_id,
+			receiver_nfts: payload.receiver_nfts,
+			received_nfts: Vec::new(),
+			timestamp: env::block_timestamp(),
+			is_monarch: false,
+			fee_paid: fee,
+			base_fee: self.required_cost.0,
+			expires_at: self.internal_expiry_deadline(),
+			status: OfferStatus::Created,
+		};
+
+		self.internal_add_offer(&payload.hash, &transaction_data);
+	}
+
+	fn internal_set_offer_balance(&mut self, account_id: &AccountId, balance: u128) {
+		if balance == 0 {
+			self.offer_balances.remove(account_id);
+		} else {
+			self.offer_balances.insert(account_id, &balance);
+		}
+	}
+}
+
+fn storage_cost_since(initial_storage: u64) -> u128 {
+	let storage_used = env::storage_usage().saturating_sub(initial_storage);
+	env::storage_byte_cost().as_yoctonear() * u128::from(storage_used)
+}
+
+// the attached deposit pays for the storage written since `initial_storage`, at least 1 yoctoNEAR
+// is required either way and anything above the cost goes back to the caller
+fn refund_storage_deposit(initial_storage: u64) {
+	let storage_cost = storage_cost_since(initial_storage);
+	let attached_deposit = env::attached_deposit().as_yoctonear();
+
+	assert!(
+		attached_deposit >= storage_cost.max(1),
+		"Attach at least {} yoctoNEAR to cover storage",
+		storage_cost.max(1)
+	);
+
+	let refund = attached_deposit - storage_cost;
+	if refund > 1 {
+		Promise::new(env::predecessor_account_id()).transfer(NearToken::from_yoctonear(refund));
+	}
+}
+
+fn verify_signature(public_key: &PublicKey, message: &[u8], signature: &[u8]) -> bool {
+	// the first byte of a near_sdk key is its curve type
+	let key: &[u8; 32] = match public_key.as_bytes()[1..].try_into() {
+		Ok(key) => key,
+		Err(_) => return false,
+	};
+
+	let signature: &[u8; 64] = match signature.try_into() {
+		Ok(signature) => signature,
+		Err(_) => return false,
+	};
+
+	env::ed25519_verify(signature, message, key)
+}
@@ -0,0 +1,94 @@
+use crate::*;
+use crate::internal::collections_of;
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub struct TradeStats {
+	pub swap_count: u64,
+	pub near_volume: U128,
+	pub fees: U128,
+}
+
+impl Default for TradeStats {
+	fn default() -> Self {
+		Self {
+			swap_count: 0,
+			near_volume: U128(0),
+			fees: U128(0),
+		}
+	}
+}
+
+impl TradeStats {
+	fn record(&mut self, near_volume: u128, fee: u128) {
+		self.swap_count += 1;
+		self.near_volume = U128(self.near_volume.0 + near_volume);
+		self.fees = U128(self.fees.0 + fee);
+	}
+}
+
+#[near_bindgen]
+impl Contract {
+
+	pub fn get_total_stats(&self) -> TradeStats {
+		self.total_stats.clone()
+	}
+
+	pub fn get_collection_stats(&self, contract_id: AccountId) -> TradeStats {
+		self.collection_stats.get(&contract_id).unwrap_or_default()
+	}
+
+	pub fn get_collections_stats(&self, from_index: Option<U128>, limit: Option<u64>) -> Vec<(AccountId, TradeStats)> {
+		let start = u128::from(from_index.unwrap_or(U128(0)));
+
+		self.collection_stats
+			.iter()
+			.skip(start as usize)
+			.take(limit.unwrap_or(50) as usize)
+			.collect()
+	}
+
+	pub fn get_account_stats(&self, account_id: AccountId) -> TradeStats {
+		self.account_stats.get(&account_id).unwrap_or_default()
+	}
+
+	pub fn get_accounts_stats(&self, from_index: Option<U128>, limit: Option<u64>) -> Vec<(AccountId, TradeStats)> {
+		let start = u128::from(from_index.unwrap_or(U128(0)));
+
+		self.account_stats
+			.iter()
+			.skip(start as usize)
+			.take(limit.unwrap_or(50) as usize)
+			.collect()
+	}
+}
+
+impl Contract {
+	// every collection and both parties in a settled swap are credited with its full NEAR volume and fee
+	pub(crate) fn internal_record_trade(&mut self, offer: &HashOffer, fee: u128) {
+		let collections = collections_of(offer.sender_nfts.iter().chain(offer.receiver_nfts.iter()));
+		self.internal_record_volume(&collections, [&offer.sender_id, &offer.receiver_id], offer.sender_near, fee);
+	}
+
+	// a sale counts as one trade for the collection, the seller and the buyer
+	pub(crate) fn internal_record_sale(&mut self, seller_id: &AccountId, buyer_id: &AccountId, token: &TokenData, price: u128, fee: u128) {
+		self.internal_record_volume(std::slice::from_ref(&token.contract_id), [seller_id, buyer_id], price, fee);
+	}
+
+	fn internal_record_volume(&mut self, collections: &[AccountId], accounts: [&AccountId; 2], near_volume: u128, fee: u128) {
+		self.total_stats.record(near_volume, fee);
+
+		for contract_id in collections {
+			let mut stats = self.collection_stats.get(contract_id).unwrap_or_default();
+			stats.record(near_volume, fee);
+			self.collection_stats.insert(contract_id, &stats);
+		}
+
+		for account_id in accounts {
+			let mut stats = self.account_stats.get(account_id).unwrap_or_default();
+			stats.record(near_volume, fee);
+			self.account_stats.insert(account_id, &stats);
+		}
+	}
+}
@@ -0,0 +1,178 @@
+use crate::*;
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub enum OfferStatus {
+	// stored, no NFT deposited yet
+	Created,
+	// some but not all NFTs deposited
+	PartiallyFunded,
+	// every NFT deposited, waiting for settlement
+	Funded,
+	// transfers issued, waiting for their results
+	Settling,
+	Settled,
+	Cancelled,
+	Expired,
+	// at least one settlement transfer failed, `retry_settlement` re-issues the transfers still outstanding
+	// and `admin_unwind_failed_offer` is the way out if they keep failing
+	Failed,
+}
+
+impl OfferStatus {
+	pub fn can_transition_to(&self, next: OfferStatus) -> bool {
+		use OfferStatus::*;
+
+		matches!(
+			(self, next),
+			(Created, PartiallyFunded) | (Created, Funded) | (Created, Cancelled) | (Created, Expired)
+			| (PartiallyFunded, PartiallyFunded) | (PartiallyFunded, Funded) | (PartiallyFunded, Cancelled) | (PartiallyFunded, Expired)
+			// an amendment that drops every deposited NFT
+			| (PartiallyFunded, Created)
+			| (Funded, Settling) | (Funded, Cancelled)
+			| (Settling, Settled) | (Settling, Failed)
+			// part of the swap was already delivered, so a failed offer is pushed forward,
+			// or unwound by support once its collection keeps rejecting the transfer
+			| (Failed, Settling) | (Failed, Cancelled)
+		)
+	}
+}
+
+impl From<OfferOutcome> for OfferStatus {
+	fn from(outcome: OfferOutcome) -> Self {
+		match outcome {
+			OfferOutcome::Settled => OfferStatus::Settled,
+			OfferOutcome::Cancelled => OfferStatus::Cancelled,
+			OfferOutcome::Expired => OfferStatus::Expired,
+		}
+	}
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct OfferCounts {
+	pub open: u64,
+	pub created: u64,
+	pub partially_funded: u64,
+	pub funded: u64,
+	pub settling: u64,
+	pub settled: u64,
+	pub cancelled: u64,
+	pub expired: u64,
+	pub failed: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct OfferSideProgress {
+	pub account_id: AccountId,
+	pub deposited_nfts: Vec<TokenData>,
+	pub outstanding_nfts: Vec<TokenData>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct OfferProgress {
+	pub status: OfferStatus,
+	pub sender: OfferSideProgress,
+	pub receiver: OfferSideProgress,
+	// the sender's NEAR, taken in full by send_offer so neither side ever owes any
+	pub near_escrowed: U128,
+	// parties that still have to deposit, empty once the offer is funded or closing
+	pub waiting_on: Vec<AccountId>,
+}
+
+#[near_bindgen]
+impl Contract {
+
+	// open offers report their live status, closed ones the outcome kept in the archive
+	pub fn get_offer_status(&self, hash: Hash) -> Option<OfferStatus> {
+		if let Some(offer) = self.hash_map.get(&hash) {
+			return Some(offer.status)
+		}
+
+		self.archive_by_hash
+			.get(&hash)
+			.and_then(|id| self.archive.get(&id))
+			.map(|record| OfferStatus::from(record.outcome))
+	}
+
+	pub fn get_offer_progress(&self, hash: Hash) -> Option<OfferProgress> {
+		let offer = self.hash_map.get(&hash)?;
+
+		let sender = side_progress(&offer.sender_id, &offer.sender_nfts, &offer.sent_nfts);
+		let receiver = side_progress(&offer.receiver_id, &offer.receiver_nfts, &offer.received_nfts);
+
+		let collecting = matches!(offer.status, OfferStatus::Created | OfferStatus::PartiallyFunded);
+
+		let waiting_on = [&sender, &receiver]
+			.iter()
+			.filter(|side| collecting && !side.outstanding_nfts.is_empty())
+			.map(|side| side.account_id.clone())
+			.collect();
+
+		Some(OfferProgress {
+			status: offer.status,
+			sender,
+			receiver,
+			near_escrowed: U128(offer.sender_near),
+			waiting_on,
+		})
+	}
+
+	pub fn get_offer_counts(&self) -> OfferCounts {
+		OfferCounts {
+			open: self.open_offers.len(),
+			created: self.status_count(OfferStatus::Created),
+			partially_funded: self.status_count(OfferStatus::PartiallyFunded),
+			funded: self.status_count(OfferStatus::Funded),
+			settling: self.status_count(OfferStatus::Settling),
+			settled: self.status_count(OfferStatus::Settled),
+			cancelled: self.status_count(OfferStatus::Cancelled),
+			expired: self.status_count(OfferStatus::Expired),
+			failed: self.status_count(OfferStatus::Failed),
+		}
+	}
+}
+
+impl Contract {
+	fn status_count(&self, status: OfferStatus) -> u64 {
+		self.status_counts.get(&status).unwrap_or(0)
+	}
+
+	pub(crate) fn internal_count_status(&mut self, status: OfferStatus) {
+		self.status_counts.insert(&status, &(self.status_count(status) + 1));
+	}
+
+	// validates the move and keeps the per-status counters in line; the caller stores the offer
+	pub(crate) fn internal_set_status(&mut self, offer: &mut HashOffer, next: OfferStatus) {
+		assert!(
+			offer.status.can_transition_to(next),
+			"Offer can't move from {:?} to {:?}",
+			offer.status,
+			next
+		);
+
+		if offer.status != next {
+			self.status_counts.insert(&offer.status, &self.status_count(offer.status).saturating_sub(1));
+			self.internal_count_status(next);
+		}
+
+		offer.status = next;
+	}
+}
+
+fn side_progress(account_id: &AccountId, expected: &[TokenData], deposited: &[TokenData]) -> OfferSideProgress {
+	let outstanding_nfts = expected
+		.iter()
+		.filter(|x| !deposited.iter().any(|y| y.contract_id == x.contract_id && y.token_id == x.token_id))
+		.cloned()
+		.collect();
+
+	OfferSideProgress {
+		account_id: account_id.clone(),
+		deposited_nfts: deposited.to_vec(),
+		outstanding_nfts,
+	}
+}
@@ -0,0 +1,188 @@
+use crate::*;
+use near_sdk::json_types::{Base58CryptoHash, Base64VecU8, U64};
+use near_sdk::{assert_one_yocto, CryptoHash, Gas, Promise};
+
+pub const DEFAULT_TIMELOCK_DELAY: u64 = 24 * 60 * 60 * 1_000_000_000; // 24 hours in nanoseconds
+
+const GAS_FOR_MIGRATE: Gas = Gas::from_tgas(30);
+
+// sensitive admin changes only take effect once their delay has passed
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub enum ParameterChange {
+	FeeSchedule {
+		required_cost: U128,
+		percentage_fee_threshold: U128,
+		percentage_fee_bps: u32,
+	},
+	FeeWallet {
+		fee_wallet: AccountId,
+	},
+	HolderCollection {
+		holder_collection: AccountId,
+	},
+	TimelockDelay {
+		delay: U64,
+	},
+	CodeUpgrade {
+		code_hash: Base58CryptoHash,
+	},
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub struct PendingChange {
+	pub id: u64,
+	pub change: ParameterChange,
+	pub proposed_by: AccountId,
+	pub proposed_at: u64,
+	pub eta: u64,
+}
+
+#[near_bindgen]
+impl Contract {
+
+	pub fn get_timelock_delay(&self) -> U64 {
+		U64(self.timelock_delay)
+	}
+
+	pub fn get_pending_change(&self, id: u64) -> Option<PendingChange> {
+		self.pending_changes.get(&id)
+	}
+
+	pub fn get_pending_changes(&self, from_index: Option<U128>, limit: Option<u64>) -> Vec<PendingChange> {
+		let start = u128::from(from_index.unwrap_or(U128(0)));
+
+		self.pending_changes
+			.values()
+			.skip(start as usize)
+			.take(limit.unwrap_or(50) as usize)
+			.collect()
+	}
+
+	#[payable]
+	pub fn propose_change(&mut self, change: ParameterChange) -> u64 {
+		assert_one_yocto();
+		self.assert_change_permission(&change);
+
+		let id = self.next_change_id;
+		self.next_change_id += 1;
+
+		let now = env::block_timestamp();
+		let pending = PendingChange {
+			id,
+			change,
+			proposed_by: env::predecessor_account_id(),
+			proposed_at: now,
+			eta: now + self.timelock_delay,
+		};
+
+		self.pending_changes.insert(&id, &pending);
+
+		env::log_str(format!("Proposed change {} executable after {}", id, pending.eta).as_str());
+		id
+	}
+
+	#[payable]
+	pub fn cancel_change(&mut self, id: u64) {
+		assert_one_yocto();
+
+		let pending = self.pending_changes.get(&id).expect("Change not found");
+		self.assert_change_permission(&pending.change);
+
+		self.pending_changes.remove(&id);
+
+		env::log_str(format!("Cancelled change {}", id).as_str());
+	}
+
+	#[payable]
+	pub fn execute_change(&mut self, id: u64) {
+		assert_one_yocto();
+
+		let pending = self.internal_take_ready_change(id);
+
+		match pending.change {
+			ParameterChange::FeeSchedule { required_cost, percentage_fee_threshold, percentage_fee_bps } => {
+				assert!(percentage_fee_bps <= 10_000, "Percentage fee can't exceed 100%");
+
+				self.required_cost = required_cost;
+				self.percentage_fee_threshold = percentage_fee_threshold;
+				self.percentage_fee_bps = percentage_fee_bps;
+			},
+			ParameterChange::FeeWallet { fee_wallet } => {
+				self.fee_wallet = fee_wallet;
+			},
+			ParameterChange::HolderCollection { holder_collection } => {
+				self.holder_collection = holder_collection;
+			},
+			ParameterChange::TimelockDelay { delay } => {
+				self.timelock_delay = delay.0;
+			},
+			ParameterChange::CodeUpgrade { .. } => {
+				env::panic_str("Code upgrades are executed through upgrade");
+			},
+		}
+
+		env::log_str(format!("Executed change {}", id).as_str());
+	}
+
+	// deploys code whose hash went through the timelock, then runs `migrate` on the new code
+	#[payable]
+	pub fn upgrade(&mut self, id: u64, code: Base64VecU8) -> Promise {
+		assert_one_yocto();
+
+		let pending = self.pending_changes.get(&id).expect("Change not found");
+
+		let code_hash = match pending.change {
+			ParameterChange::CodeUpgrade { code_hash } => CryptoHash::from(code_hash),
+			_ => env::panic_str("Change is not a code upgrade"),
+		};
+
+		assert!(
+			env::sha256(&code.0) == code_hash.to_vec(),
+			"Code does not match the proposed hash"
+		);
+
+		self.internal_take_ready_change(id);
+
+		env::log_str(format!("Upgrading to code from change {}", id).as_str());
+
+		Promise::new(env::current_account_id())
+			.deploy_contract(code.0)
+			.function_call("migrate".to_string(), vec![], NearToken::from_yoctonear(0), GAS_FOR_MIGRATE)
+	}
+
+	// re-reads the stored state after an upgrade, extend this when the state layout changes
+	#[private]
+	#[init(ignore_state)]
+	pub fn migrate() -> Self {
+		env::state_read().expect("Contract is not initialized")
+	}
+}
+
+impl Contract {
+	// fee changes are delegated to fee managers, everything else stays with the owner
+	fn assert_change_permission(&self, change: &ParameterChange) {
+		match change {
+			ParameterChange::FeeSchedule { .. } | ParameterChange::FeeWallet { .. } => self.assert_role(Role::FeeManager),
+			_ => self.assert_owner(),
+		}
+	}
+
+	fn internal_take_ready_change(&mut self, id: u64) -> PendingChange {
+		let pending = self.pending_changes.get(&id).expect("Change not found");
+		self.assert_change_permission(&pending.change);
+
+		assert!(
+			env::block_timestamp() >= pending.eta,
+			"Change {} is timelocked until {}",
+			id,
+			pending.eta
+		);
+
+		self.pending_changes.remove(&id);
+		pending
+	}
+}
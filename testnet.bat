@@ -1,4 +1,10 @@
 set NEAR_ENV=testnet
 set CONTRACT_ID=betaswap.testnet
+rem change me
+set OWNER_ID=%CONTRACT_ID%
+set FEE_WALLET=%CONTRACT_ID%
+set HOLDER_COLLECTION=%CONTRACT_ID%
 
-near deploy --wasmFile target/out/main.wasm --accountId %CONTRACT_ID%
\ No newline at end of file
+rem the state layout is not compatible with the original contract, so CONTRACT_ID has to be a fresh account.
+rem deploy and init run as one batch, on an account that still holds old state the whole batch fails
+near deploy --wasmFile target/out/main.wasm --accountId %CONTRACT_ID% --initFunction new --initArgs "{\"owner_id\":\"%OWNER_ID%\",\"fee_wallet\":\"%FEE_WALLET%\",\"required_cost\":\"100000000000000000000000\",\"holder_collection\":\"%HOLDER_COLLECTION%\",\"archive_retention\":null,\"timelock_delay\":\"60000000000\"}"
\ No newline at end of file